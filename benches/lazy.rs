@@ -0,0 +1,59 @@
+//! Compares deserializing documents directly with [LazyDocs] against parsing them into a
+//! `serde_yml::Value` with [LazyValues] first.
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde::Deserialize;
+use syt::lazy::{LazyDocs, LazyValues};
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Event {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    source: Source,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Source {
+    host: String,
+    port: u16,
+}
+
+/// Generates a large multi-document fixture.
+fn fixture(docs: u64) -> String {
+    (0..docs)
+        .map(|id| {
+            format!(
+                "id: {id}\nname: event {id}\ntags:\n- alpha\n- beta\nsource:\n  host: host-{}\n  port: {}\n",
+                id % 16,
+                8000 + id % 100
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn lazy_docs(c: &mut Criterion) {
+    let yaml = fixture(10_000);
+    let mut group = c.benchmark_group("lazy");
+    group.throughput(Throughput::Bytes(yaml.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("docs_direct", |b| {
+        b.iter(|| LazyDocs::<Event, _>::from_str(black_box(&yaml)).count())
+    });
+    group.bench_function("values_then_from_value", |b| {
+        b.iter(|| {
+            LazyValues::from_str(black_box(&yaml))
+                .filter_map(|value| serde_yml::from_value::<Event>(value).ok())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lazy_docs);
+criterion_main!(benches);
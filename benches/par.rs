@@ -0,0 +1,68 @@
+//! Compares deserializing documents sequentially with [LazyDocs] against deserializing them in
+//! parallel with [LazyDocs::par_iter], in order and as they are ready.
+use std::hint::black_box;
+use std::io::Cursor;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde::Deserialize;
+use syt::lazy::LazyDocs;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Event {
+    id: u64,
+    name: String,
+    tags: Vec<String>,
+    source: Source,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Source {
+    host: String,
+    port: u16,
+}
+
+/// Generates a large multi-document fixture.
+fn fixture(docs: u64) -> String {
+    (0..docs)
+        .map(|id| {
+            format!(
+                "id: {id}\nname: event {id}\ntags:\n- alpha\n- beta\nsource:\n  host: host-{}\n  port: {}\n",
+                id % 16,
+                8000 + id % 100
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn par_docs(c: &mut Criterion) {
+    let yaml = fixture(10_000);
+    let mut group = c.benchmark_group("par");
+    group.throughput(Throughput::Bytes(yaml.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| LazyDocs::<Event, _>::from_reader(Cursor::new(black_box(yaml.clone()))).count())
+    });
+    group.bench_function("par_ordered", |b| {
+        b.iter(|| {
+            LazyDocs::<Event, _>::from_reader(Cursor::new(black_box(yaml.clone())))
+                .par_iter()
+                .count()
+        })
+    });
+    group.bench_function("par_unordered", |b| {
+        b.iter(|| {
+            LazyDocs::<Event, _>::from_reader(Cursor::new(black_box(yaml.clone())))
+                .par_iter()
+                .ordered(false)
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, par_docs);
+criterion_main!(benches);
@@ -1,16 +1,31 @@
 //! Provides a function to append a YAML doc to a file.
-use std::io::Write;
-use std::{fs::File, path::Path};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
 
+use crate::boundary::DocBounds;
 use crate::comments::KeyData;
+use crate::compress::Compression;
 
 /// Appends serialized YAML data to a file, creating the file if it doesn't exist.
 ///
 /// If the file already exists and contains data, a `---` separator is added before
 /// appending the new data.  This allows for multiple YAML documents to be stored within a single file.
 ///
+/// If the file is compressed with gzip or zstd, or is new and has a `.gz` or `.zst` extension, the
+/// separator and data are appended as a new gzip member or zstd frame. This needs the `gzip` or
+/// `zstd` feature, without which appending to such a file is an error. See [crate::compress].
+///
+/// The file is locked while the document is appended, waiting for any other process appending
+/// to it. Use [AppendOptions] to not wait. See [Lock].
+///
+/// The document is not synced to disk, so it may be lost on a crash of the operating system or
+/// a power loss. Use [AppendOptions::durability] to sync it. See [Durability].
+///
 /// # Arguments
 ///
 /// * `path` - The path to the file.
@@ -56,13 +71,32 @@ use crate::comments::KeyData;
 /// # }
 /// ```
 pub fn append_or_new<T: Serialize>(path: &Path, t: T) -> crate::Result<()> {
-    let mut file = File::options().append(true).create(true).open(path)?;
-    let metadata = file.metadata()?;
-    if metadata.len() != 0 {
-        file.write_all(b"\n---\n")?;
-    }
-    serde_yml::ser::to_writer(file, &t)?;
-    Ok(())
+    AppendOptions::new().append(path, t)
+}
+
+/// Appends serialized YAML data to a file, creating the file with a header document if it doesn't
+/// exist.
+///
+/// The header is only written when the file is new or empty. If the file already has data it is
+/// left alone, and `t` is appended as with [append_or_new]. Files written this way can be read
+/// with [crate::lazy::LazyDocs::with_header].
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `header` - The header to write as the first document of a new file.
+/// * `t` - The data to serialize and append, which must implement the `Serialize` trait from `serde`.
+///
+/// # Returns
+///
+/// * `Ok(())` if the operation is successful.
+/// * An error if the file cannot be opened, written to, or the serialization fails.
+pub fn append_or_new_with_header<H: Serialize, T: Serialize>(
+    path: &Path,
+    header: H,
+    t: T,
+) -> crate::Result<()> {
+    AppendOptions::new().append_with_header(path, header, t)
 }
 
 /// Appends serialized YAML data to a file, creating the file if it doesn't exist, with comments.
@@ -70,6 +104,8 @@ pub fn append_or_new<T: Serialize>(path: &Path, t: T) -> crate::Result<()> {
 /// If the file already exists and contains data, a `---` separator is added before
 /// appending the new data.  This allows for multiple YAML documents to be stored within a single file.
 ///
+/// Compressed files are handled as for [append_or_new].
+///
 /// See [crate::comments::to_writer] for limitations.
 ///
 /// # Arguments
@@ -132,26 +168,720 @@ pub fn append_or_new_with_comments<T: Serialize, F>(path: &Path, t: T, cb: F) ->
 where
     F: Fn(KeyData) -> Option<String>,
 {
-    let mut file = File::options().append(true).create(true).open(path)?;
-    let metadata = file.metadata()?;
-    if metadata.len() != 0 {
-        file.write_all(b"\n---\n")?;
+    AppendOptions::new().append_with_comments(path, t, cb)
+}
+
+/// Appends serialized YAML data to a file with a tag on its `---` line, creating the file if it
+/// doesn't exist.
+///
+/// The document is always preceded by a `--- <tag>` line, even when the file is new, so that it
+/// can be dispatched by its tag with [crate::tagged::TaggedDocs].
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `tag` - The tag, including the leading `!`, such as `!Deployment`.
+/// * `t` - The data to serialize and append, which must implement the `Serialize` trait from `serde`.
+///
+/// # Returns
+///
+/// * `Ok(())` if the operation is successful.
+/// * An error if the tag is not a single `!` tag, or the file cannot be opened, written to, or
+///   the serialization fails.
+pub fn append_or_new_tagged<T: Serialize>(path: &Path, tag: &str, t: T) -> crate::Result<()> {
+    AppendOptions::new().append_tagged(path, tag, t)
+}
+
+/// Appends serialized YAML documents to a file, creating the file if it doesn't exist.
+///
+/// The documents are serialized with `---` separators between them, and before them if the
+/// file has data, into a single buffer that is written with one call. If any document fails to
+/// serialize, none are written. With a [Durability] set in [AppendOptions], the file is synced
+/// once after the write.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `items` - The documents to serialize and append, which must implement the `Serialize` trait
+///   from `serde`.
+///
+/// # Returns
+///
+/// * The number of documents appended.
+/// * An error if the file cannot be opened, written to, or the serialization fails.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use syt::append::{append_all, append_or_new};
+/// use syt::lazy::LazyDocs;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// struct Event {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("events.yml");
+/// append_or_new(&path, Event { id: 0 })?;
+///
+/// let count = append_all(&path, (1..4).map(|id| Event { id }))?;
+///
+/// assert_eq!(count, 3);
+/// assert_eq!(LazyDocs::<Event>::new(&path)?.count(), 4);
+/// # Ok(())
+/// # }
+/// ```
+pub fn append_all<T, I>(path: &Path, items: I) -> crate::Result<usize>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    AppendOptions::new().append_all(path, items)
+}
+
+/// Appends serialized YAML documents with comments to a file, creating the file if it doesn't
+/// exist.
+///
+/// See [append_all] and [crate::comments::to_writer].
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `items` - The documents to serialize and append, which must implement the `Serialize` trait
+///   from `serde`.
+/// * `cb` - A callback function that takes a [`KeyData`] argument and returns an optional
+///   comment for the key, which is called for the keys of every document.
+///
+/// # Returns
+///
+/// * The number of documents appended.
+/// * An error if the file cannot be opened, written to, or the serialization fails.
+pub fn append_all_with_comments<T, I, F>(path: &Path, items: I, cb: F) -> crate::Result<usize>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+    F: Fn(KeyData) -> Option<String>,
+{
+    AppendOptions::new().append_all_with_comments(path, items, cb)
+}
+
+/// How an append waits for other processes appending to the same file.
+///
+/// The lock is an advisory lock on the whole file (`flock` on Unix, `LockFileEx` on Windows). It
+/// is held from checking whether the file is empty until the document has been written, so that
+/// appends by processes using this crate neither interleave nor both write the first document.
+/// Writers that don't take the lock are not stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lock {
+    /// Doesn't lock the file.
+    None,
+    /// Waits for as long as another process holds the lock. This is the default.
+    #[default]
+    Blocking,
+    /// Fails with an [io::ErrorKind::WouldBlock] error if another process holds the lock.
+    Try,
+    /// Waits for up to the duration, then fails with an [io::ErrorKind::TimedOut] error.
+    Timeout(Duration),
+}
+
+/// Options for appending YAML documents to a file.
+///
+/// The `append_or_new` functions use the default options: they wait for the lock and do not
+/// sync.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use serde::Serialize;
+/// use syt::append::{AppendOptions, Durability, Lock};
+/// use syt::Error;
+///
+/// #[derive(Serialize)]
+/// struct Event {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("events.yml");
+///
+/// let options = AppendOptions::new()
+///     .lock(Lock::Timeout(Duration::from_secs(1)))
+///     .durability(Durability::DataAndDirectory);
+/// options.append(&path, Event { id: 1 })?;
+/// options.append(&path, Event { id: 2 })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AppendOptions {
+    lock: Lock,
+    durability: Durability,
+}
+
+impl AppendOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how appends wait for other processes appending to the same file.
+    pub fn lock(mut self, lock: Lock) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Sets what each append syncs to disk before returning.
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Appends serialized YAML documents to a file, creating the file if it doesn't exist.
+    ///
+    /// See [append_all].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, locked, written to, or synced, or the
+    /// serialization of any document fails.
+    pub fn append_all<T, I>(&self, path: &Path, items: I) -> crate::Result<usize>
+    where
+        T: Serialize,
+        I: IntoIterator<Item = T>,
+    {
+        let mut appender = self.open(path)?;
+        let count = appender.append_all_with(items, |writer, t| {
+            Ok(serde_yml::ser::to_writer(writer, &t)?)
+        })?;
+        appender.sync_durable()?;
+        Ok(count)
+    }
+
+    /// Appends serialized YAML documents with comments to a file, creating the file if it
+    /// doesn't exist.
+    ///
+    /// See [append_all_with_comments].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, locked, written to, or synced, or the
+    /// serialization of any document fails.
+    pub fn append_all_with_comments<T, I, F>(
+        &self,
+        path: &Path,
+        items: I,
+        cb: F,
+    ) -> crate::Result<usize>
+    where
+        T: Serialize,
+        I: IntoIterator<Item = T>,
+        F: Fn(KeyData) -> Option<String>,
+    {
+        let mut appender = self.open(path)?;
+        let count = appender.append_all_with(items, |writer, t| {
+            crate::comments::to_writer(writer, &t, &cb)
+        })?;
+        appender.sync_durable()?;
+        Ok(count)
+    }
+
+    /// Appends serialized YAML data to a file, creating the file if it doesn't exist.
+    ///
+    /// See [append_or_new].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, locked, written to, or synced, or the
+    /// serialization fails.
+    pub fn append<T: Serialize>(&self, path: &Path, t: T) -> crate::Result<()> {
+        self.append_with(path, None, |writer, _| {
+            Ok(serde_yml::ser::to_writer(writer, &t)?)
+        })
+    }
+
+    /// Appends serialized YAML data to a file, creating the file with a header document if it
+    /// doesn't exist.
+    ///
+    /// See [append_or_new_with_header].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, locked, written to, or synced, or the
+    /// serialization fails.
+    pub fn append_with_header<H: Serialize, T: Serialize>(
+        &self,
+        path: &Path,
+        header: H,
+        t: T,
+    ) -> crate::Result<()> {
+        self.append_with(path, None, |writer, new| {
+            if new {
+                serde_yml::ser::to_writer(&mut *writer, &header)?;
+                writer.write_all(b"\n---\n")?;
+            }
+            Ok(serde_yml::ser::to_writer(writer, &t)?)
+        })
+    }
+
+    /// Appends serialized YAML data with comments to a file, creating the file if it doesn't
+    /// exist.
+    ///
+    /// See [append_or_new_with_comments].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, locked, written to, or synced, or the
+    /// serialization fails.
+    pub fn append_with_comments<T: Serialize, F>(
+        &self,
+        path: &Path,
+        t: T,
+        cb: F,
+    ) -> crate::Result<()>
+    where
+        F: Fn(KeyData) -> Option<String>,
+    {
+        self.append_with(path, None, |writer, _| {
+            crate::comments::to_writer(writer, &t, cb)
+        })
+    }
+
+    /// Appends serialized YAML data to a file with a tag on its `---` line, creating the file if
+    /// it doesn't exist.
+    ///
+    /// See [append_or_new_tagged].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tag is not a single `!` tag, or the file cannot be opened,
+    /// locked, written to, or synced, or the serialization fails.
+    pub fn append_tagged<T: Serialize>(&self, path: &Path, tag: &str, t: T) -> crate::Result<()> {
+        if !tag.starts_with('!') || tag.contains(char::is_whitespace) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid YAML tag: {tag:?}"),
+            )
+            .into());
+        }
+        self.append_with(path, Some(tag), |writer, _| {
+            Ok(serde_yml::ser::to_writer(writer, &t)?)
+        })
+    }
+
+    fn append_with<F>(&self, path: &Path, tag: Option<&str>, write_doc: F) -> crate::Result<()>
+    where
+        F: FnOnce(&mut dyn Write, bool) -> crate::Result<()>,
+    {
+        let mut appender = self.open(path)?;
+        appender.append_with(tag, write_doc)?;
+        Ok(appender.sync_durable()?)
+    }
+
+    /// Opens the file at `path` for appending with these options.
+    pub(crate) fn open(&self, path: &Path) -> crate::Result<Appender> {
+        Appender::open(path, self)
+    }
+}
+
+/// A file open for appending YAML documents.
+pub(crate) struct Appender {
+    file: File,
+    compression: Compression,
+    lock: Lock,
+    durability: Durability,
+    /// Whether the file has no data.
+    new: bool,
+    /// The directory of a file that was created when it was opened, until it is synced.
+    unsynced_dir: Option<PathBuf>,
+}
+
+impl Appender {
+    /// Opens the file at `path` for appending, creating it if it doesn't exist.
+    pub(crate) fn open(path: &Path, options: &AppendOptions) -> crate::Result<Self> {
+        let (mut file, created, readable) = loop {
+            match File::options()
+                .read(true)
+                .append(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(file) => break (file, true, true),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => match open_existing(path)
+                {
+                    Ok((file, readable)) => break (file, false, readable),
+                    // removed since, so try to create it again
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err.into()),
+                },
+                Err(err) => return Err(err.into()),
+            }
+        };
+        // a file that cannot be read is assumed to be plain text
+        let compression = if readable {
+            Compression::detect_file(&mut file, path)?
+        } else {
+            Compression::None
+        };
+        compression.check_supported()?;
+        let new = file.metadata()?.len() == 0;
+        let unsynced_dir = created.then(|| parent_dir(path));
+        Ok(Appender {
+            file,
+            compression,
+            lock: options.lock,
+            durability: options.durability,
+            new,
+            unsynced_dir,
+        })
+    }
+
+    /// Syncs the file as required by its [Durability], if any.
+    pub(crate) fn sync_durable(&mut self) -> io::Result<()> {
+        match self.durability {
+            Durability::None => Ok(()),
+            Durability::Data | Durability::DataAndDirectory => self.sync(),
+        }
+    }
+
+    /// Raises its [Durability] to [Durability::Data] if it syncs nothing.
+    pub(crate) fn sync_data_at_least(&mut self) {
+        if self.durability == Durability::None {
+            self.durability = Durability::Data;
+        }
+    }
+
+    /// Syncs the file's data, and its directory if required by its [Durability] and the file
+    /// was created.
+    fn sync(&mut self) -> io::Result<()> {
+        sync_data(&self.file)?;
+        if self.durability == Durability::DataAndDirectory {
+            if let Some(dir) = &self.unsynced_dir {
+                sync_dir(dir)?;
+                self.unsynced_dir = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Locks the file, and appends a separator if it has data and then the document.
+    ///
+    /// With a `tag`, the separator has the tag and is written even if the file is new.
+    /// `write_doc` is told whether the file is new, that is, whether it has no data.
+    pub(crate) fn append_with<F>(&mut self, tag: Option<&str>, write_doc: F) -> crate::Result<()>
+    where
+        F: FnOnce(&mut dyn Write, bool) -> crate::Result<()>,
+    {
+        self.write_locked(|buf, new| {
+            let separator = match (new, tag) {
+                (true, None) => String::new(),
+                (true, Some(tag)) => format!("--- {tag}\n"),
+                (false, None) => "\n---\n".to_string(),
+                (false, Some(tag)) => format!("\n--- {tag}\n"),
+            };
+            buf.extend_from_slice(separator.as_bytes());
+            write_doc(buf, new)
+        })
+    }
+
+    /// Locks the file, and appends the documents written by `write_doc` for each item with
+    /// separators between them, and before them if the file has data.
+    ///
+    /// Returns the number of documents appended.
+    pub(crate) fn append_all_with<I, F>(
+        &mut self,
+        items: I,
+        mut write_doc: F,
+    ) -> crate::Result<usize>
+    where
+        I: IntoIterator,
+        F: FnMut(&mut dyn Write, I::Item) -> crate::Result<()>,
+    {
+        self.write_locked(|buf, new| {
+            let mut count = 0;
+            for item in items {
+                if count > 0 || !new {
+                    buf.extend_from_slice(b"\n---\n");
+                }
+                write_doc(buf, item)?;
+                count += 1;
+            }
+            Ok(count)
+        })
+    }
+
+    /// Locks the file and appends what `fill` writes to a buffer, given whether the file is new.
+    ///
+    /// The buffer is written with a single call, so a serialization error writes nothing and
+    /// readers never see part of a document unless the process dies during the write.
+    /// Compressed documents are written as a single member or frame.
+    fn write_locked<F, R>(&mut self, fill: F) -> crate::Result<R>
+    where
+        F: FnOnce(&mut Vec<u8>, bool) -> crate::Result<R>,
+    {
+        let _locked = Locked::new(&self.file, self.lock)?;
+        if self.lock != Lock::None {
+            // another process may have appended since the last check
+            self.new = self.file.metadata()?.len() == 0;
+        }
+        let mut buf = Vec::new();
+        let result = fill(&mut buf, self.new)?;
+        if !buf.is_empty() {
+            let encoded = self.compression.encode(&buf)?;
+            (&self.file).write_all(&encoded)?;
+            self.new = false;
+        }
+        Ok(result)
+    }
+}
+
+/// Holds the lock on a file until dropped.
+struct Locked<'a>(Option<&'a File>);
+
+impl<'a> Locked<'a> {
+    fn new(file: &'a File, mode: Lock) -> io::Result<Self> {
+        if mode == Lock::None {
+            return Ok(Locked(None));
+        }
+        lock(file, mode)?;
+        Ok(Locked(Some(file)))
+    }
+}
+
+impl Drop for Locked<'_> {
+    fn drop(&mut self) {
+        if let Some(file) = self.0 {
+            let _ = file.unlock();
+        }
+    }
+}
+
+/// A torn document removed from the end of a file by [repair].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repaired {
+    /// The zero-based index of the removed document.
+    pub index: usize,
+    /// The one-based line on which the removed document started.
+    pub start_line: usize,
+    /// The length the file was truncated to.
+    pub len: u64,
+    /// The bytes removed from the end of the file, which include the separator and any blank
+    /// lines before the document.
+    pub removed: Vec<u8>,
+}
+
+/// Removes a torn document from the end of a YAML file, such as one left by a process that died
+/// while appending to it.
+///
+/// The last document is torn if the file does not end with a line ending, or the document is
+/// empty, not UTF-8, or not valid YAML. It is removed along with the blank lines before it, so
+/// the file ends as it did before the document was appended. A torn document that happens to be
+/// valid YAML and end with a line ending cannot be told apart from a complete one, and is kept.
+///
+/// The file is locked while it is repaired, waiting for any process appending to it.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, locked, or truncated, or is compressed.
+///
+/// Example:
+///
+/// ```rust
+/// use syt::append::{append_or_new, repair};
+/// use syt::lazy::LazyDocs;
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("events.yml");
+/// std::fs::write(&path, "id: 1\n\n---\nid: [2")?;
+///
+/// let repaired = repair(&path)?.expect("a torn document");
+/// assert_eq!(repaired.index, 1);
+/// assert_eq!(repaired.removed, b"\n---\nid: [2");
+///
+/// append_or_new(&path, 3)?;
+/// assert_eq!(std::fs::read_to_string(&path)?, "id: 1\n\n---\n3\n");
+/// # Ok(())
+/// # }
+/// ```
+pub fn repair(path: &Path) -> crate::Result<Option<Repaired>> {
+    let mut file = File::options().read(true).write(true).open(path)?;
+    file.lock()?;
+    if Compression::detect_file(&mut file, path)? != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "compressed files cannot be repaired",
+        )
+        .into());
     }
-    crate::comments::to_writer(file, &t, cb)?;
+    let mut reader = BufReader::new(&file);
+    let mut bounds = DocBounds::default();
+    let mut line = Vec::new();
+    // the offset is counted here, as invalid UTF-8 changes the length of the text
+    let mut offset = 0u64;
+    // the last document, and the end of the last line with content before it
+    let mut doc = Vec::new();
+    let mut kept = 0u64;
+    let mut content_end = 0u64;
+    let mut line_ended = true;
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if bounds.push(&text).is_some() {
+            doc.clear();
+            kept = content_end;
+        }
+        doc.extend_from_slice(&line);
+        offset += n as u64;
+        line_ended = line.ends_with(b"\n");
+        if !text.trim().is_empty() {
+            content_end = offset;
+        }
+    }
+    let torn = !line_ended
+        || (!bounds.has_content() && bounds.start_line() > 0)
+        || std::str::from_utf8(&doc).map_or(true, |doc| {
+            serde_yml::from_str::<serde_yml::Value>(doc).is_err()
+        });
+    if offset == 0 || !torn {
+        return Ok(None);
+    }
+    drop(reader);
+    let mut removed = Vec::new();
+    file.seek(SeekFrom::Start(kept))?;
+    file.read_to_end(&mut removed)?;
+    file.set_len(kept)?;
+    Ok(Some(Repaired {
+        index: bounds.index(),
+        start_line: bounds.start_line() + 1,
+        len: kept,
+        removed,
+    }))
+}
+
+/// What an append syncs to disk before returning, so that it survives a crash of the operating
+/// system or a power loss.
+///
+/// Without syncing, an appended document is visible to other processes as soon as it is written,
+/// but may still be lost from the operating system's cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Leaves writing to disk to the operating system. This is the default.
+    #[default]
+    None,
+    /// Syncs the file's data with [File::sync_data] (`fdatasync` on Linux).
+    Data,
+    /// Syncs the file's data and, when the append created the file, the directory containing
+    /// it, so that the new directory entry survives too. Directories are only synced on Unix.
+    DataAndDirectory,
+}
+
+/// The syncs made by appends on the current thread, for tests.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Synced {
+    Data,
+    Directory(PathBuf),
+}
+
+#[cfg(test)]
+thread_local! {
+    pub(crate) static SYNCED: std::cell::RefCell<Vec<Synced>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+fn sync_data(file: &File) -> io::Result<()> {
+    #[cfg(test)]
+    SYNCED.with_borrow_mut(|synced| synced.push(Synced::Data));
+    file.sync_data()
+}
+
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(test)]
+    SYNCED.with_borrow_mut(|synced| synced.push(Synced::Directory(dir.to_path_buf())));
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
     Ok(())
 }
 
+/// Opens an existing file for reading and appending, or only for appending if it cannot be read.
+///
+/// Returns the file and whether it can be read.
+fn open_existing(path: &Path) -> io::Result<(File, bool)> {
+    match File::options().read(true).append(true).open(path) {
+        Ok(file) => Ok((file, true)),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            Ok((File::options().append(true).open(path)?, false))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The directory containing the file at `path`.
+pub(crate) fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Takes the lock on an open file.
+fn lock(file: &File, lock: Lock) -> io::Result<()> {
+    match lock {
+        Lock::None => Ok(()),
+        Lock::Blocking => file.lock(),
+        Lock::Try => Ok(file.try_lock()?),
+        Lock::Timeout(timeout) => {
+            let deadline = Instant::now() + timeout;
+            let mut wait = Duration::from_millis(1);
+            loop {
+                match file.try_lock() {
+                    Ok(()) => return Ok(()),
+                    Err(std::fs::TryLockError::WouldBlock) => {}
+                    Err(std::fs::TryLockError::Error(err)) => return Err(err),
+                }
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out waiting for the file lock",
+                    ));
+                }
+                thread::sleep(wait.min(deadline - now));
+                wait = (wait * 2).min(Duration::from_millis(50));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fs::{self, File};
-    use std::io::Read;
+    use std::io::{self, Read};
     use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
     use tempfile::NamedTempFile;
 
     use serde::{Deserialize, Serialize};
 
-    use crate::append::append_or_new;
+    use crate::append::{
+        append_all, append_all_with_comments, append_or_new, parent_dir, repair, AppendOptions,
+        Durability, Lock, Repaired, Synced, SYNCED,
+    };
     use crate::lazy::LazyDocs;
 
     #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
@@ -308,4 +1038,320 @@ mod test {
         assert_eq!(actual, Some(new_data));
         Ok(())
     }
+
+    /// Run by [concurrent_appenders_in_processes] in each child process.
+    #[test]
+    fn child_appender() {
+        let (Ok(path), Ok(worker)) = (
+            std::env::var("SYT_APPEND_CHILD_PATH"),
+            std::env::var("SYT_APPEND_CHILD_WORKER"),
+        ) else {
+            return;
+        };
+        let worker = worker.parse::<i32>().unwrap();
+        for i in 0..50 {
+            let data = TestData {
+                a: worker * 1000 + i,
+                b: "x".repeat(4096),
+            };
+            append_or_new(std::path::Path::new(&path), &data).unwrap();
+        }
+    }
+
+    #[test]
+    fn concurrent_appenders_in_processes() {
+        // GIVEN worker processes appending to the same new file
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("shared.yml");
+        let exe = std::env::current_exe().unwrap();
+        let children = (0..8)
+            .map(|worker| {
+                std::process::Command::new(&exe)
+                    .args(["--exact", "append::test::child_appender", "--quiet"])
+                    .env("SYT_APPEND_CHILD_PATH", &path)
+                    .env("SYT_APPEND_CHILD_WORKER", worker.to_string())
+                    .stdout(std::process::Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // WHEN they finish
+        for mut child in children {
+            assert!(child.wait().unwrap().success());
+        }
+
+        // THEN every document reads back intact
+        let docs = LazyDocs::<TestData>::new(&path)
+            .unwrap()
+            .try_iter()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        let mut ids = docs.iter().map(|d| d.a).collect::<Vec<_>>();
+        ids.sort();
+        let expected = (0..8)
+            .flat_map(|worker| (0..50).map(move |i| worker * 1000 + i))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+        assert!(docs.iter().all(|d| d.b.len() == 4096));
+    }
+
+    #[test]
+    fn try_and_timeout_locks() -> crate::Result<()> {
+        // GIVEN a file locked by another handle
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("locked.yml");
+        let holder = File::create(&path)?;
+        holder.lock()?;
+        let data = TestData {
+            a: 1,
+            b: "hello".to_string(),
+        };
+
+        // WHEN appended to without waiting
+        let result = AppendOptions::new().lock(Lock::Try).append(&path, &data);
+
+        // THEN it would block
+        assert!(
+            matches!(result, Err(crate::Error::IoError(err)) if err.kind() == io::ErrorKind::WouldBlock)
+        );
+
+        // WHEN appended to with a timeout
+        let result = AppendOptions::new()
+            .lock(Lock::Timeout(Duration::from_millis(20)))
+            .append(&path, &data);
+
+        // THEN it times out
+        assert!(
+            matches!(result, Err(crate::Error::IoError(err)) if err.kind() == io::ErrorKind::TimedOut)
+        );
+
+        // WHEN the lock is released
+        holder.unlock()?;
+        AppendOptions::new().lock(Lock::Try).append(&path, &data)?;
+
+        // THEN the append succeeds
+        assert_eq!(fs::read_to_string(&path)?, "a: 1\nb: hello\n");
+        Ok(())
+    }
+
+    /// Fails to serialize after its first field.
+    struct Failing;
+
+    impl Serialize for Failing {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{Error, SerializeMap};
+            let mut map = serializer.serialize_map(None)?;
+            map.serialize_entry("a", &1)?;
+            Err(S::Error::custom("failed"))
+        }
+    }
+
+    #[test]
+    fn failed_serialization_writes_nothing() -> crate::Result<()> {
+        // GIVEN a file with a document
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("events.yml");
+        append_or_new(&path, 1)?;
+
+        // WHEN a document fails to serialize partway
+        let result = append_or_new(&path, Failing);
+
+        // THEN nothing is appended
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path)?, "1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn repair_torn_documents() -> crate::Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("events.yml");
+        let cases: [(&[u8], usize, usize, &[u8]); 5] = [
+            // cut off mid line
+            (b"a: 1\n\n---\na: 2\nb: he", 1, 3, b"a: 1\n"),
+            // invalid YAML
+            (
+                b"a: 1\n\n---\na: 2\n\n---\na: [3,\n",
+                2,
+                6,
+                b"a: 1\n\n---\na: 2\n",
+            ),
+            // cut off mid character
+            (b"a: 1\n\n---\nb: \xe2\x82", 1, 3, b"a: 1\n"),
+            // only the separator
+            (b"a: 1\n\n---\n", 1, 3, b"a: 1\n"),
+            // the only document
+            (b"a: [1", 0, 1, b""),
+        ];
+        for (contents, index, start_line, expected) in cases {
+            // GIVEN a file with a torn last document
+            fs::write(&path, contents)?;
+
+            // WHEN repaired
+            let repaired = repair(&path)?.unwrap();
+
+            // THEN the document and the blank lines before it are removed
+            assert_eq!(fs::read(&path)?, expected);
+            assert_eq!(
+                repaired,
+                Repaired {
+                    index,
+                    start_line,
+                    len: expected.len() as u64,
+                    removed: contents[expected.len()..].to_vec(),
+                }
+            );
+
+            // THEN appending continues the file
+            append_or_new(&path, 9)?;
+            let docs = LazyDocs::<serde_yml::Value>::new(&path)
+                .unwrap()
+                .try_iter()
+                .collect::<crate::Result<Vec<_>>>()?;
+            assert_eq!(docs.len(), index + 1);
+            assert_eq!(docs[index], serde_yml::Value::from(9));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn repair_intact_files() -> crate::Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("events.yml");
+        for contents in ["", "a: 1\n\n---\na: 2\n", "# only a comment\n"] {
+            // GIVEN a file without a torn document
+            fs::write(&path, contents)?;
+
+            // WHEN repaired
+            let repaired = repair(&path)?;
+
+            // THEN it is unchanged
+            assert_eq!(repaired, None);
+            assert_eq!(fs::read_to_string(&path)?, contents);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn append_all_matches_append_or_new() -> crate::Result<()> {
+        // GIVEN a file written one document at a time
+        let tmp_dir = tempfile::tempdir()?;
+        let expected = tmp_dir.path().join("expected.yml");
+        for a in 0..4 {
+            append_or_new(&expected, TestData { a, b: "x".into() })?;
+        }
+
+        // WHEN the same documents are appended in batches, the first to a new file
+        let path = tmp_dir.path().join("events.yml");
+        let first = append_all(&path, (0..2).map(|a| TestData { a, b: "x".into() }))?;
+        let empty = append_all(&path, Vec::<TestData>::new())?;
+        let second = AppendOptions::new()
+            .durability(Durability::Data)
+            .append_all(&path, (2..4).map(|a| TestData { a, b: "x".into() }))?;
+
+        // THEN the files are the same
+        assert_eq!((first, empty, second), (2, 0, 2));
+        assert_eq!(fs::read_to_string(&path)?, fs::read_to_string(&expected)?);
+        Ok(())
+    }
+
+    #[test]
+    fn append_all_failure_writes_nothing() -> crate::Result<()> {
+        // GIVEN a file with a document
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("events.yml");
+        append_or_new(&path, 1)?;
+
+        // WHEN a batch fails to serialize partway
+        let result =
+            AppendOptions::new().append_all_with_comments(&path, [Ok(2), Err(Failing)], |_| None);
+
+        // THEN nothing is appended
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path)?, "1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn append_all_with_comments_comments_every_document() -> crate::Result<()> {
+        // GIVEN a new file
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("events.yml");
+
+        // WHEN documents are appended with comments
+        let count = append_all_with_comments(
+            &path,
+            (1..3).map(|a| TestData { a, b: "x".into() }),
+            |key| (key.str == "a").then(|| "the a".to_string()),
+        )?;
+
+        // THEN every document has the comments
+        assert_eq!(count, 2);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "# the a\na: 1\nb: x\n\n---\n# the a\na: 2\nb: x\n"
+        );
+        Ok(())
+    }
+
+    /// Takes the syncs made on this thread since the last call.
+    fn take_synced() -> Vec<Synced> {
+        SYNCED.with_borrow_mut(std::mem::take)
+    }
+
+    #[test]
+    fn durability_syncs() -> crate::Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let dir = tmp_dir.path().to_path_buf();
+        take_synced();
+
+        // GIVEN no durability
+        // WHEN a new file is appended to
+        append_or_new(&dir.join("none.yml"), 1)?;
+
+        // THEN nothing is synced
+        assert_eq!(take_synced(), vec![]);
+
+        // GIVEN data durability
+        let data = AppendOptions::new().durability(Durability::Data);
+
+        // WHEN a new file is appended to
+        data.append(&dir.join("data.yml"), 1)?;
+
+        // THEN only the data is synced
+        assert_eq!(take_synced(), vec![Synced::Data]);
+
+        // GIVEN data and directory durability
+        let path = dir.join("both.yml");
+        let both = AppendOptions::new().durability(Durability::DataAndDirectory);
+
+        // WHEN a new file is appended to
+        both.append(&path, 1)?;
+
+        // THEN the data and directory are synced
+        assert_eq!(take_synced(), vec![Synced::Data, Synced::Directory(dir)]);
+
+        // WHEN the existing file is appended to, one and several documents at a time
+        both.append(&path, 2)?;
+        both.append_all(&path, [3, 4, 5])?;
+
+        // THEN only the data is synced, once per call
+        assert_eq!(take_synced(), vec![Synced::Data, Synced::Data]);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "1\n\n---\n2\n\n---\n3\n\n---\n4\n\n---\n5\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parent_dir_of_relative_path() {
+        // GIVEN paths with and without directories
+        // WHEN their directory is found
+        // THEN a bare file name is in the current directory
+        assert_eq!(parent_dir(Path::new("a/b.yml")), PathBuf::from("a"));
+        assert_eq!(parent_dir(Path::new("/b.yml")), PathBuf::from("/"));
+        assert_eq!(parent_dir(Path::new("b.yml")), PathBuf::from("."));
+    }
 }
@@ -0,0 +1,338 @@
+//! Provides a handle for appending many YAML documents to a file without reopening it.
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::append::{AppendOptions, Appender};
+use crate::comments::KeyData;
+
+/// When a [YamlAppender] syncs the documents it has appended to disk.
+///
+/// What is synced is set by the [crate::append::Durability] of the options the appender was
+/// opened with. A policy other than [SyncPolicy::Never] set with [YamlAppender::sync_policy]
+/// syncs at least the data, even with [crate::append::Durability::None], the default. Without
+/// one, an appender with [crate::append::Durability::None] syncs nothing, as the
+/// [crate::append] functions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Only syncs on calls to [YamlAppender::flush].
+    Never,
+    /// Syncs after every `n` documents.
+    EveryN(usize),
+    /// Syncs after every document, as the [crate::append] functions do. This is the default.
+    #[default]
+    EveryDoc,
+}
+
+/// An open YAML file that documents of type `T` are appended to.
+///
+/// The [crate::append] functions open the file and check whether it is empty for every
+/// document. A `YamlAppender` opens the file once and keeps track of whether a `---` separator
+/// is needed. Each document is appended as by [crate::append::append_or_new]: it is serialized
+/// to a buffer and written with a single call while the file is locked. When the file is locked,
+/// whether it is empty is checked again, as other processes may have appended to it.
+///
+/// Documents are not buffered, so once [YamlAppender::append] returns, the document is visible
+/// to readers. Whether it survives a crash of the operating system depends on the
+/// [crate::append::Durability] of the options it was opened with, which sets what is synced,
+/// and the [SyncPolicy], which sets when.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use syt::append::{AppendOptions, Durability};
+/// use syt::appender::{SyncPolicy, YamlAppender};
+/// use syt::lazy::LazyDocs;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// struct Event {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("events.yml");
+///
+/// let options = AppendOptions::new().durability(Durability::Data);
+/// let mut appender =
+///     YamlAppender::<Event>::open_with(&path, &options)?.sync_policy(SyncPolicy::EveryN(100));
+/// for id in 0..1000 {
+///     appender.append(&Event { id })?;
+/// }
+/// appender.flush()?;
+///
+/// assert_eq!(LazyDocs::<Event>::new(&path)?.count(), 1000);
+/// # Ok(())
+/// # }
+/// ```
+pub struct YamlAppender<T: Serialize> {
+    appender: Appender,
+    sync_policy: SyncPolicy,
+    /// The number of documents appended since the last sync.
+    unsynced: usize,
+    phantom: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize> YamlAppender<T> {
+    /// Opens the YAML file at `path` for appending, creating it if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, or is compressed and the cargo feature for
+    /// its compression is not enabled.
+    pub fn open(path: &Path) -> crate::Result<Self> {
+        Self::open_with(path, &AppendOptions::new())
+    }
+
+    /// Opens the YAML file at `path` for appending with `options`, creating it if it doesn't
+    /// exist.
+    ///
+    /// See [YamlAppender::open].
+    pub fn open_with(path: &Path, options: &AppendOptions) -> crate::Result<Self> {
+        Ok(YamlAppender {
+            appender: options.open(path)?,
+            sync_policy: SyncPolicy::default(),
+            unsynced: 0,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets when appended documents are synced to disk.
+    ///
+    /// A policy other than [SyncPolicy::Never] syncs at least the file's data, as with
+    /// [crate::append::Durability::Data], if the appender was opened without durability.
+    pub fn sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        if sync_policy != SyncPolicy::Never {
+            self.appender.sync_data_at_least();
+        }
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    /// Appends a document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be locked, written to, or synced, or the
+    /// serialization fails. Nothing is written if the serialization fails.
+    pub fn append(&mut self, t: &T) -> crate::Result<()> {
+        self.appender
+            .append_with(None, |writer, _| Ok(serde_yml::ser::to_writer(writer, t)?))?;
+        self.appended()
+    }
+
+    /// Appends a document with comments.
+    ///
+    /// See [crate::comments::to_writer] for the callback.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be locked, written to, or synced, or the
+    /// serialization fails. Nothing is written if the serialization fails.
+    pub fn append_with_comments<F>(&mut self, t: &T, cb: F) -> crate::Result<()>
+    where
+        F: Fn(KeyData) -> Option<String>,
+    {
+        self.appender
+            .append_with(None, |writer, _| crate::comments::to_writer(writer, t, cb))?;
+        self.appended()
+    }
+
+    /// Syncs the documents appended since the last sync to disk, as set by the
+    /// [crate::append::Durability] of the options it was opened with and its [SyncPolicy]. With
+    /// [crate::append::Durability::None] and no policy syncing documents, this does nothing.
+    ///
+    /// Documents are written to the file as they are appended, so this is only needed for them
+    /// to survive a crash of the operating system. Dropping a `YamlAppender` does not sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be synced.
+    pub fn flush(&mut self) -> crate::Result<()> {
+        if self.unsynced > 0 {
+            self.appender.sync_durable()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    fn appended(&mut self) -> crate::Result<()> {
+        self.unsynced += 1;
+        match self.sync_policy {
+            SyncPolicy::Never => Ok(()),
+            SyncPolicy::EveryN(n) if self.unsynced < n => Ok(()),
+            SyncPolicy::EveryN(_) | SyncPolicy::EveryDoc => self.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::append::{append_or_new, Durability, Lock, Synced, SYNCED};
+    use crate::lazy::LazyDocs;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Event {
+        id: u32,
+        name: String,
+    }
+
+    fn event(id: u32) -> Event {
+        Event {
+            id,
+            name: format!("event {id}"),
+        }
+    }
+
+    #[test]
+    fn test_matches_append_or_new() {
+        // GIVEN a file written with append_or_new
+        let dir = tempfile::tempdir().unwrap();
+        let expected = dir.path().join("expected.yml");
+        append_or_new(&expected, event(1)).unwrap();
+        append_or_new(&expected, event(2)).unwrap();
+
+        // WHEN the same documents are appended with a YamlAppender
+        let path = dir.path().join("events.yml");
+        let mut appender = YamlAppender::open(&path).unwrap();
+        appender.append(&event(1)).unwrap();
+        appender.append(&event(2)).unwrap();
+
+        // WHEN they are appended without locking, so that the separator is only tracked
+        let unlocked = dir.path().join("unlocked.yml");
+        let options = AppendOptions::new().lock(Lock::None);
+        let mut unlocked_appender = YamlAppender::open_with(&unlocked, &options).unwrap();
+        unlocked_appender.append(&event(1)).unwrap();
+        unlocked_appender.append(&event(2)).unwrap();
+
+        // THEN the files are the same
+        let expected = std::fs::read_to_string(&expected).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        assert_eq!(std::fs::read_to_string(&unlocked).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_existing_file_and_other_writers() {
+        // GIVEN an appender for a file with a document
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+        let mut appender = YamlAppender::open(&path).unwrap();
+
+        // WHEN it appends while another writer empties and appends to the file
+        appender.append(&event(2)).unwrap();
+        std::fs::write(&path, "").unwrap();
+        appender
+            .append_with_comments(&event(3), |key| {
+                (key.str == "id").then(|| "the id".to_string())
+            })
+            .unwrap();
+        append_or_new(&path, event(4)).unwrap();
+        appender.append(&event(5)).unwrap();
+
+        // THEN the separators follow the file
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# the id\nid: 3\nname: event 3\n\n---\nid: 4\nname: event 4\n\n---\nid: 5\nname: event 5\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_policy() {
+        // GIVEN appenders with each sync policy
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        let policies = [
+            (SyncPolicy::Never, 3),
+            (SyncPolicy::EveryN(2), 1),
+            (SyncPolicy::EveryDoc, 0),
+        ];
+        for (policy, unsynced) in policies {
+            let mut appender = YamlAppender::open(&path).unwrap().sync_policy(policy);
+
+            // WHEN three documents are appended
+            for id in 0..3 {
+                appender.append(&event(id)).unwrap();
+            }
+
+            // THEN the documents after the last sync are unsynced until flushed
+            assert_eq!(appender.unsynced, unsynced);
+            appender.flush().unwrap();
+            assert_eq!(appender.unsynced, 0);
+        }
+        assert_eq!(LazyDocs::<Event>::new(&path).unwrap().count(), 9);
+    }
+
+    #[test]
+    fn test_durability() {
+        let dir = tempfile::tempdir().unwrap();
+        let take_synced = || SYNCED.with_borrow_mut(std::mem::take);
+
+        // GIVEN an appender with the default options
+        let mut appender = YamlAppender::open(&dir.path().join("none.yml")).unwrap();
+        take_synced();
+
+        // WHEN it appends and flushes
+        appender.append(&event(1)).unwrap();
+        appender.flush().unwrap();
+
+        // THEN nothing is synced
+        assert_eq!(take_synced(), vec![]);
+
+        // GIVEN an appender with data durability and the default policy
+        let options = AppendOptions::new().durability(Durability::Data);
+        let mut appender = YamlAppender::open_with(&dir.path().join("data.yml"), &options).unwrap();
+
+        // WHEN it appends twice
+        appender.append(&event(1)).unwrap();
+        appender.append(&event(2)).unwrap();
+
+        // THEN every document is synced
+        assert_eq!(take_synced(), vec![Synced::Data, Synced::Data]);
+
+        // GIVEN an appender with the default options that syncs every other document
+        let mut appender = YamlAppender::open(&dir.path().join("every_n.yml"))
+            .unwrap()
+            .sync_policy(SyncPolicy::EveryN(2));
+
+        // WHEN it appends twice
+        appender.append(&event(1)).unwrap();
+        appender.append(&event(2)).unwrap();
+
+        // THEN the data is synced despite the options
+        assert_eq!(take_synced(), vec![Synced::Data]);
+
+        // GIVEN an appender for a new file with data and directory durability that only syncs
+        // when flushed
+        let options = AppendOptions::new().durability(Durability::DataAndDirectory);
+        let mut appender = YamlAppender::open_with(&dir.path().join("both.yml"), &options)
+            .unwrap()
+            .sync_policy(SyncPolicy::Never);
+
+        // WHEN it appends and flushes twice
+        appender.append(&event(1)).unwrap();
+        assert_eq!(take_synced(), vec![]);
+        appender.flush().unwrap();
+        appender.append(&event(2)).unwrap();
+        appender.flush().unwrap();
+
+        // THEN the directory is only synced the first time
+        assert_eq!(
+            take_synced(),
+            vec![
+                Synced::Data,
+                Synced::Directory(dir.path().to_path_buf()),
+                Synced::Data
+            ]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Provides lazy streams over YAML documents for async readers.
+//!
+//! These are the async counterparts of the iterators in [crate::lazy] and split documents in the
+//! same way. They implement [Stream] over any tokio [AsyncBufRead] and are available with the
+//! `tokio` feature.
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use serde::de::DeserializeOwned;
+use serde_yml::Value;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, BufReader};
+
+use crate::boundary::DocBounds;
+use crate::lazy::RawDoc;
+
+/// A lazy stream over YAML documents read from an async reader.
+///
+/// This is the async version of [crate::lazy::LazyDocs]. Documents that cannot be deserialized
+/// are skipped. Use [AsyncLazyDocs::try_stream] to have them reported.
+///
+/// Example:
+///
+/// ```rust
+/// use futures::StreamExt;
+/// use serde::Deserialize;
+/// use syt::async_lazy::AsyncLazyDocs;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct MyDoc {
+///     id: u32,
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let reader: &[u8] = b"id: 1\n---\nid: 2\n";
+///
+/// let docs = AsyncLazyDocs::<MyDoc, _>::from_reader(reader)
+///     .collect::<Vec<_>>()
+///     .await;
+///
+/// assert_eq!(docs, vec![MyDoc { id: 1 }, MyDoc { id: 2 }]);
+/// # }
+/// ```
+pub struct AsyncLazyDocs<T: DeserializeOwned, R: AsyncBufRead + Unpin = BufReader<File>> {
+    doc_start: AsyncLazyDocStart<R>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> AsyncLazyDocs<T> {
+    /// Creates a new `AsyncLazyDocs` stream over the YAML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub async fn new(path: &Path) -> crate::Result<Self> {
+        Ok(Self::from_reader(BufReader::new(File::open(path).await?)))
+    }
+}
+
+impl<T: DeserializeOwned, R: AsyncBufRead + Unpin> AsyncLazyDocs<T, R> {
+    /// Creates a new `AsyncLazyDocs` stream over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R) -> Self {
+        AsyncLazyDocs {
+            doc_start: AsyncLazyDocStart::from_reader(reader),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Converts this stream into one that yields a [crate::Result] for every document.
+    ///
+    /// See [crate::lazy::LazyDocs::try_iter].
+    pub fn try_stream(self) -> TryAsyncLazyDocs<T, R> {
+        TryAsyncLazyDocs { docs: self }
+    }
+
+    fn poll_try_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<crate::Result<T>>> {
+        let raw = match ready!(self.doc_start.poll_next_raw(cx)) {
+            Some(Ok(raw)) => raw,
+            Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(
+            serde_yml::from_str::<T>(&raw.text).map_err(|err| raw.error(err)),
+        ))
+    }
+}
+
+impl<T: DeserializeOwned, R: AsyncBufRead + Unpin> Stream for AsyncLazyDocs<T, R> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(this.poll_try_next(cx)) {
+                Some(Ok(t)) => return Poll::Ready(Some(t)),
+                Some(Err(crate::Error::DocumentError(_))) => continue,
+                _ => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// A fallible version of [AsyncLazyDocs], created with [AsyncLazyDocs::try_stream].
+pub struct TryAsyncLazyDocs<T: DeserializeOwned, R: AsyncBufRead + Unpin = BufReader<File>> {
+    docs: AsyncLazyDocs<T, R>,
+}
+
+impl<T: DeserializeOwned, R: AsyncBufRead + Unpin> Stream for TryAsyncLazyDocs<T, R> {
+    type Item = crate::Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().docs.poll_try_next(cx)
+    }
+}
+
+/// A lazy stream over YAML values read from an async reader.
+///
+/// This is the async version of [crate::lazy::LazyValues]. Documents that cannot be parsed are
+/// skipped. Use [AsyncLazyValues::try_stream] to have them reported.
+pub struct AsyncLazyValues<R: AsyncBufRead + Unpin = BufReader<File>> {
+    docs: AsyncLazyDocs<Value, R>,
+}
+
+impl AsyncLazyValues {
+    /// Creates a new `AsyncLazyValues` stream over the YAML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub async fn new(path: &Path) -> crate::Result<Self> {
+        Ok(Self::from_reader(BufReader::new(File::open(path).await?)))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncLazyValues<R> {
+    /// Creates a new `AsyncLazyValues` stream over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R) -> Self {
+        AsyncLazyValues {
+            docs: AsyncLazyDocs::from_reader(reader),
+        }
+    }
+
+    /// Converts this stream into one that yields a [crate::Result] for every document.
+    pub fn try_stream(self) -> TryAsyncLazyDocs<Value, R> {
+        self.docs.try_stream()
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncLazyValues<R> {
+    type Item = Value;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().docs).poll_next(cx)
+    }
+}
+
+/// A lazy stream that yields strings representing YAML documents from an async reader.
+///
+/// This is the async version of [crate::lazy::LazyDocStart].
+pub struct AsyncLazyDocStart<R: AsyncBufRead + Unpin = BufReader<File>> {
+    reader: R,
+    bounds: DocBounds,
+    /// The bytes of a line that has not been terminated yet.
+    line_buf: Vec<u8>,
+    /// The text of the current document.
+    text: String,
+    done: bool,
+}
+
+impl AsyncLazyDocStart {
+    /// Creates a new `AsyncLazyDocStart` stream over the YAML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub async fn new(path: &Path) -> crate::Result<Self> {
+        Ok(Self::from_reader(BufReader::new(File::open(path).await?)))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncLazyDocStart<R> {
+    /// Creates a new `AsyncLazyDocStart` stream over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R) -> Self {
+        AsyncLazyDocStart {
+            reader,
+            bounds: DocBounds::default(),
+            line_buf: Vec::new(),
+            text: String::new(),
+            done: false,
+        }
+    }
+
+    /// Reads the next document along with its location.
+    ///
+    /// An I/O error is returned once, after which no more documents are read.
+    fn poll_next_raw(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<RawDoc>>> {
+        while !self.done {
+            match ready!(self.poll_read_line(cx)) {
+                Ok(Some(line)) => {
+                    let span = self.bounds.push(&line);
+                    let doc = span.and_then(|span| span.raw(std::mem::take(&mut self.text)));
+                    self.text.push_str(&line);
+                    if let Some(doc) = doc {
+                        return Poll::Ready(Some(Ok(doc)));
+                    }
+                }
+                Ok(None) => {
+                    self.done = true;
+                    let text = std::mem::take(&mut self.text);
+                    return Poll::Ready(self.bounds.finish().raw(text).map(Ok));
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+        Poll::Ready(None)
+    }
+
+    /// Reads a line including its line ending, or `None` at the end of the stream.
+    fn poll_read_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<String>>> {
+        loop {
+            let buf = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+            if buf.is_empty() {
+                if self.line_buf.is_empty() {
+                    return Poll::Ready(Ok(None));
+                }
+                break;
+            }
+            let (used, found) = match buf.iter().position(|b| *b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (buf.len(), false),
+            };
+            self.line_buf.extend_from_slice(&buf[..used]);
+            Pin::new(&mut self.reader).consume(used);
+            if found {
+                break;
+            }
+        }
+        let bytes = std::mem::take(&mut self.line_buf);
+        Poll::Ready(
+            String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        )
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncLazyDocStart<R> {
+    type Item = String;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let raw = ready!(self.get_mut().poll_next_raw(cx));
+        Poll::Ready(raw.and_then(|raw| raw.ok()).map(|raw| raw.text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use serde::Deserialize;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::lazy::LazyDocStart;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct TestDoc {
+        title: String,
+    }
+
+    const CORPUS: &str = "# comment\n---\ntitle: Doc 1\n...\n%YAML 1.2\n--- !tag\ntitle: |\n  ---\n  text\n---\n---\ntitle: Doc 3\r\n";
+
+    #[tokio::test]
+    async fn test_same_splitting_as_sync() {
+        // GIVEN a corpus of edge cases read in tiny chunks
+        let reader = BufReader::with_capacity(3, CORPUS.as_bytes());
+
+        // WHEN split by the async stream
+        let docs = AsyncLazyDocStart::from_reader(reader)
+            .collect::<Vec<_>>()
+            .await;
+
+        // THEN the documents are the same as the sync iterator
+        let expected = LazyDocStart::from_str(CORPUS).collect::<Vec<_>>();
+        assert_eq!(docs, expected);
+        assert_eq!(docs.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lazy_docs_from_reader() {
+        // GIVEN an in-memory reader with a bad document between good ones
+        let reader: &[u8] = b"title: Doc 1\n---\nbad: doc\n---\ntitle: Doc 3\n";
+
+        // WHEN streaming the docs
+        let docs = AsyncLazyDocs::<TestDoc, _>::from_reader(reader)
+            .map(|doc| doc.title)
+            .collect::<Vec<_>>()
+            .await;
+
+        // THEN the bad document is skipped
+        assert_eq!(docs, vec!["Doc 1", "Doc 3"]);
+    }
+
+    #[tokio::test]
+    async fn test_lazy_docs_try_stream() {
+        // GIVEN an in-memory reader with a bad document between good ones
+        let reader: &[u8] = b"title: Doc 1\n---\nbad: doc\n---\ntitle: Doc 3\n";
+
+        // WHEN streaming the docs with try_stream
+        let docs = AsyncLazyDocs::<TestDoc, _>::from_reader(reader)
+            .try_stream()
+            .collect::<Vec<_>>()
+            .await;
+
+        // THEN the bad document is reported with its location
+        assert_eq!(docs.len(), 3);
+        assert!(matches!(
+            &docs[1],
+            Err(crate::Error::DocumentError(err)) if err.index == 1 && err.start_line == 2 && err.end_line == 3
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lazy_values_from_pipe() {
+        // GIVEN a pipe written to in pieces
+        let (reader, mut writer) = tokio::io::duplex(8);
+        let write = async move {
+            for piece in ["a: 1\n-", "--\na:", " 2\n"] {
+                writer.write_all(piece.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+
+        // WHEN streaming the values
+        let read = AsyncLazyValues::from_reader(BufReader::new(reader)).collect::<Vec<_>>();
+        let ((), values) = tokio::join!(write, read);
+
+        // THEN both values are read
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["a"], Value::from(1));
+        assert_eq!(values[1]["a"], Value::from(2));
+    }
+
+    #[tokio::test]
+    async fn test_lazy_docs_from_file() {
+        // GIVEN a file with two documents
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "title: Doc 1\n---\ntitle: Doc 2\n").unwrap();
+
+        // WHEN streaming the docs from the file
+        let docs = AsyncLazyDocs::<TestDoc>::new(file.path())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        // THEN both docs are read
+        assert_eq!(docs.len(), 2);
+    }
+}
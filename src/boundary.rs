@@ -0,0 +1,461 @@
+//! YAML-aware detection of document boundaries in a multi-document stream.
+//!
+//! This is not a YAML parser. It looks at one line at a time and knows just enough about YAML
+//! to tell where one document ends and the next begins:
+//!
+//! * `---` starts a document only when it is at the start of a line and is followed by
+//!   whitespace or the end of the line. Anything after it (such as `--- !tag` or `--- value`)
+//!   is part of the new document.
+//! * `...` ends a document. Content after it begins a new (bare) document.
+//! * `%YAML` and `%TAG` directives belong to the document that follows them.
+//! * Lines inside a block scalar (`|` or `>`) are content, even when they are at the start of
+//!   a line, unless they are a document marker.
+//! * Comments and blank lines before the first `---` belong to the document that follows.
+use crate::lazy::RawDoc;
+
+/// Splits a stream of lines into documents.
+#[derive(Debug, Default)]
+struct Splitter {
+    /// The current document has an explicit `---` marker.
+    marker: bool,
+    /// The current document has content.
+    content: bool,
+    /// The current document has been ended with `...`.
+    ended: bool,
+    /// The indentation of the node owning an open block scalar, `-1` for the top level.
+    block: Option<isize>,
+}
+
+impl Splitter {
+    /// Accounts for the next line (without its line ending) and returns `true` if the line
+    /// begins a new document.
+    ///
+    /// When `true` is returned the lines pushed before this one form a complete document.
+    fn push(&mut self, line: &str) -> bool {
+        let indent = (line.len() - line.trim_start_matches(' ').len()) as isize;
+        let is_marker = marker_rest(line, "---").is_some() || marker_rest(line, "...").is_some();
+
+        if let Some(parent) = self.block {
+            if !is_marker && (line.trim().is_empty() || indent > parent) {
+                return false;
+            }
+            self.block = None;
+        }
+
+        if let Some(rest) = marker_rest(line, "---") {
+            let start = self.marker || self.content;
+            if start {
+                *self = Splitter::default();
+            }
+            self.marker = true;
+            let rest = strip_comment(rest).trim();
+            if !rest.is_empty() {
+                self.content = true;
+                self.open_block(rest, -1);
+            }
+            return start;
+        }
+
+        if marker_rest(line, "...").is_some() {
+            self.ended = true;
+            return false;
+        }
+
+        if line.starts_with('%') {
+            let start = self.marker || self.content;
+            if start {
+                *self = Splitter::default();
+            }
+            return start;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return false;
+        }
+
+        let start = self.ended;
+        if start {
+            *self = Splitter::default();
+        }
+        let parent = if !self.content && is_node_properties_only(trimmed) {
+            -1
+        } else {
+            indent
+        };
+        self.content = true;
+        self.open_block(strip_comment(line).trim(), parent);
+        start
+    }
+
+    /// Returns `true` if the current document has content.
+    ///
+    /// A document made only of markers, directives, comments and blank lines has no content.
+    fn has_content(&self) -> bool {
+        self.content
+    }
+
+    fn open_block(&mut self, text: &str, parent: isize) {
+        if ends_with_block_header(text) {
+            self.block = Some(parent);
+        }
+    }
+}
+
+/// Locates and numbers the documents in a stream of lines, split as by [Splitter].
+///
+/// This is the bookkeeping shared by everything that splits documents. Documents without
+/// content, made only of markers, directives, comments and blank lines, are not numbered.
+#[derive(Debug, Default)]
+pub(crate) struct DocBounds {
+    splitter: Splitter,
+    /// The index of the current document.
+    index: usize,
+    /// The number of lines and bytes pushed.
+    line: usize,
+    offset: u64,
+    /// The number of lines and bytes before the current document.
+    start_line: usize,
+    start_offset: u64,
+}
+
+impl DocBounds {
+    /// Starts at a document with index `index`, preceded by `line` lines and `offset` bytes.
+    pub(crate) fn at(index: usize, line: usize, offset: u64) -> Self {
+        DocBounds {
+            index,
+            line,
+            offset,
+            start_line: line,
+            start_offset: offset,
+            ..DocBounds::default()
+        }
+    }
+
+    /// Accounts for the next line, including its line ending. If the line begins a new document,
+    /// returns the location of the document it ends.
+    pub(crate) fn push(&mut self, line: &str) -> Option<DocSpan> {
+        let had_content = self.splitter.has_content();
+        let span = self
+            .splitter
+            .push(trim_line_ending(line))
+            .then(|| self.end(had_content));
+        self.line += 1;
+        self.offset += line.len() as u64;
+        span
+    }
+
+    /// Ends the current document at the end of the stream, returning its location.
+    pub(crate) fn finish(&mut self) -> DocSpan {
+        let span = self.end(self.splitter.has_content());
+        self.splitter = Splitter::default();
+        span
+    }
+
+    fn end(&mut self, has_content: bool) -> DocSpan {
+        let span = DocSpan {
+            index: has_content.then_some(self.index),
+            offset: self.start_offset,
+            start_line: self.start_line + 1,
+            end_line: self.line,
+        };
+        self.index += usize::from(has_content);
+        self.start_line = self.line;
+        self.start_offset = self.offset;
+        span
+    }
+
+    /// Returns `true` if the current document has content.
+    pub(crate) fn has_content(&self) -> bool {
+        self.splitter.has_content()
+    }
+
+    /// The index of the current document.
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of lines pushed.
+    pub(crate) fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The number of bytes pushed.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The number of lines before the current document.
+    pub(crate) fn start_line(&self) -> usize {
+        self.start_line
+    }
+
+    /// The number of bytes before the current document.
+    pub(crate) fn start_offset(&self) -> u64 {
+        self.start_offset
+    }
+}
+
+/// The location of a document found by [DocBounds].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DocSpan {
+    /// The index of the document, or `None` if it has no content.
+    index: Option<usize>,
+    offset: u64,
+    start_line: usize,
+    end_line: usize,
+}
+
+impl DocSpan {
+    /// Attaches the text of the document, or returns `None` if it has no content.
+    pub(crate) fn raw<S>(self, text: S) -> Option<RawDoc<S>> {
+        Some(RawDoc {
+            index: self.index?,
+            offset: self.offset,
+            start_line: self.start_line,
+            end_line: self.end_line,
+            text,
+        })
+    }
+}
+
+/// If `line` is the given document marker, returns the text after the marker.
+pub(crate) fn marker_rest<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(marker)?;
+    if rest.is_empty() || rest.starts_with([' ', '\t']) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Removes the `\n` or `\r\n` line ending from a line.
+pub(crate) fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Removes a trailing comment, which starts with a `#` preceded by whitespace.
+pub(crate) fn strip_comment(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'#' && (i == 0 || bytes[i - 1] == b' ' || bytes[i - 1] == b'\t') {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+/// Returns `true` if the text ends with a block scalar header such as `|`, `>-` or `|2+`.
+fn ends_with_block_header(text: &str) -> bool {
+    let token = text.rsplit([' ', '\t']).next().unwrap_or_default();
+    let mut chars = token.chars();
+    matches!(chars.next(), Some('|' | '>'))
+        && chars.all(|c| c == '+' || c == '-' || c.is_ascii_digit())
+}
+
+/// Returns `true` if everything before the last token is anchors or tags, meaning that the
+/// last token is the top-level node of the document.
+fn is_node_properties_only(text: &str) -> bool {
+    let mut tokens = text.split_whitespace().collect::<Vec<_>>();
+    tokens.pop();
+    tokens
+        .iter()
+        .all(|t| t.starts_with('!') || t.starts_with('&'))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yml::Value;
+
+    use crate::lazy::LazyDocStart;
+
+    fn split(s: &str) -> Vec<String> {
+        LazyDocStart::from_str(s).collect()
+    }
+
+    #[test]
+    fn test_marker_must_be_followed_by_whitespace() {
+        // GIVEN lines that start with marker characters but are not markers
+        let s = "a: |\n  x\n---foo\n....\nb: 1\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN it is one document
+        assert_eq!(docs, vec![s.to_string()]);
+    }
+
+    #[test]
+    fn test_marker_inside_indented_block_scalar() {
+        // GIVEN a block scalar containing a `---` line
+        let s = "a: |\n  ---\n  text\n---\nb: 1\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the block scalar is kept whole
+        assert_eq!(docs, vec!["a: |\n  ---\n  text\n", "---\nb: 1\n"]);
+        let value: Value = serde_yml::from_str(&docs[0]).unwrap();
+        assert_eq!(value["a"], Value::from("---\ntext\n"));
+    }
+
+    #[test]
+    fn test_top_level_block_scalar() {
+        // GIVEN a top-level block scalar with content at the start of lines (as in example 9.5 of
+        // the YAML 1.2 spec)
+        let s = "--- |\n%not a directive\n---not a marker\n--- >\n#not a comment\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the content is not mistaken for a directive, marker or comment
+        assert_eq!(
+            docs,
+            vec![
+                "--- |\n%not a directive\n---not a marker\n",
+                "--- >\n#not a comment\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bare_top_level_block_scalar() {
+        // GIVEN a bare document that is a block scalar
+        let s = "|\n%text\n...\n%YAML 1.2\n---\na: 1\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the directive after the end marker starts the next document
+        assert_eq!(docs, vec!["|\n%text\n...\n", "%YAML 1.2\n---\na: 1\n"]);
+    }
+
+    #[test]
+    fn test_block_scalar_ends_at_lesser_indentation() {
+        // GIVEN a nested block scalar followed by a directive
+        let s = "a:\n  b: >-\n    text\n%YAML 1.2\n---\nc: 1\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the directive is not part of the block scalar
+        assert_eq!(
+            docs,
+            vec!["a:\n  b: >-\n    text\n", "%YAML 1.2\n---\nc: 1\n"]
+        );
+    }
+
+    #[test]
+    fn test_document_end_marker() {
+        // GIVEN documents ended with `...` and no `---`
+        let s = "a: 1\n...\nb: 2\n...\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN each bare document is separate
+        assert_eq!(docs, vec!["a: 1\n...\n", "b: 2\n...\n"]);
+    }
+
+    #[test]
+    fn test_directives_belong_to_next_document() {
+        // GIVEN documents with directives
+        let s = "%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\na: 1\n...\n%YAML 1.2\n---\nb: 2\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the directives start each document
+        assert_eq!(
+            docs,
+            vec![
+                "%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\na: 1\n...\n",
+                "%YAML 1.2\n---\nb: 2\n"
+            ]
+        );
+        for doc in docs {
+            serde_yml::from_str::<Value>(&doc).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_directive_without_end_marker() {
+        // GIVEN a directive directly after a document
+        let s = "a: 1\n%YAML 1.2\n---\nb: 2\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the directive starts the next document
+        assert_eq!(docs, vec!["a: 1\n", "%YAML 1.2\n---\nb: 2\n"]);
+    }
+
+    #[test]
+    fn test_marker_header_content() {
+        // GIVEN markers with tags and values
+        let s = "--- !tag\na: 1\n--- !tag\nb: 2\n--- value\n--- # comment\nc: 3\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN every document keeps its own marker line
+        assert_eq!(
+            docs,
+            vec![
+                "--- !tag\na: 1\n",
+                "--- !tag\nb: 2\n",
+                "--- value\n",
+                "--- # comment\nc: 3\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leading_comments_belong_to_first_document() {
+        // GIVEN comments before the first marker
+        let s = "# comment\n\n---\na: 1\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN it is one document
+        assert_eq!(docs, vec![s.to_string()]);
+    }
+
+    #[test]
+    fn test_empty_documents_are_skipped() {
+        // GIVEN empty documents
+        let s = "---\n---\n# nothing\n---\na: 1\n---\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN only the document with content is yielded
+        assert_eq!(docs, vec!["---\na: 1\n"]);
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        // GIVEN a stream with CRLF line endings
+        let s = "a: 1\r\n---\r\nb: 2\r\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the line endings are kept
+        assert_eq!(docs, vec!["a: 1\r\n", "---\r\nb: 2\r\n"]);
+    }
+
+    #[test]
+    fn test_block_scalar_header_with_comment() {
+        // GIVEN a block scalar header followed by a comment
+        let s = "--- | # text\n%text\n";
+
+        // WHEN split
+        let docs = split(s);
+
+        // THEN the block scalar is recognized
+        assert_eq!(docs, vec![s.to_string()]);
+    }
+}
@@ -291,7 +291,7 @@ pub struct KeyData<'a> {
     pub start: usize,
 }
 
-fn get_key_name(str: &str) -> Option<KeyData> {
+fn get_key_name(str: &str) -> Option<KeyData<'_>> {
     let mut start: Option<usize> = None;
     let mut end: Option<usize> = None;
     for (i, c) in str.char_indices() {
@@ -315,9 +315,7 @@ fn get_key_name(str: &str) -> Option<KeyData> {
             start = Some(i);
         }
     }
-    if start.is_some() && end.is_some() {
-        let start = start.unwrap(); // checked above
-        let end = end.unwrap(); // checked above
+    if let (Some(start), Some(end)) = (start, end) {
         let s = &str[start..=end];
         Some(KeyData { str: s, start })
     } else {
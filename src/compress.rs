@@ -0,0 +1,386 @@
+//! Provides transparent decompression of YAML files and compression of appended documents.
+//!
+//! Compressed files are detected by their magic bytes, or by their extension when they are empty
+//! or do not exist yet. Gzip files (`.gz`) need the `gzip` feature and zstd files (`.zst`) need
+//! the `zstd` feature. Without the feature, reading or appending to such a file is an error.
+//! Appends to a file that cannot be read, but can be written, assume it is plain YAML.
+//!
+//! Documents appended to a compressed file are written as a new gzip member or zstd frame, so
+//! the file stays a valid concatenated stream that decompresses to the same text as an
+//! uncompressed file.
+use std::borrow::Cow;
+use std::fs::File;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::io::Write;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression of a YAML file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain text.
+    None,
+    /// Gzip, which needs the `gzip` feature.
+    Gzip,
+    /// Zstandard, which needs the `zstd` feature.
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression of the file at `path`.
+    ///
+    /// The magic bytes at the start of the file are used if it has any content, otherwise the
+    /// extension of the path is used.
+    pub fn detect(path: &Path) -> io::Result<Compression> {
+        match File::open(path) {
+            Ok(mut file) => Self::detect_file(&mut file, path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::from_extension(path)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Detects the compression of an open file, leaving it positioned at the start.
+    pub(crate) fn detect_file(file: &mut File, path: &Path) -> io::Result<Compression> {
+        let mut magic = [0; 4];
+        let mut len = 0;
+        file.seek(SeekFrom::Start(0))?;
+        while len < magic.len() {
+            match file.read(&mut magic[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        file.seek(SeekFrom::Start(0))?;
+        if len == 0 {
+            Ok(Self::from_extension(path))
+        } else {
+            Ok(Self::from_magic(&magic[..len]))
+        }
+    }
+
+    /// Returns the compression indicated by the extension of `path`.
+    pub fn from_extension(path: &Path) -> Compression {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Returns the compression indicated by the first bytes of a file.
+    pub fn from_magic(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Compresses `bytes` as a single gzip member or zstd frame.
+    ///
+    /// The result is written with a single call, so that appends without a lock do not
+    /// interleave, and a failure to compress writes nothing.
+    pub(crate) fn encode(self, bytes: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Compression::None => Ok(Cow::Borrowed(bytes)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(Cow::Owned(encoder.finish()?))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 0)?;
+                encoder.write_all(bytes)?;
+                Ok(Cow::Owned(encoder.finish()?))
+            }
+            #[cfg(not(feature = "gzip"))]
+            Compression::Gzip => Err(self.unsupported("gzip")),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(self.unsupported("zstd")),
+        }
+    }
+
+    /// Returns an error if the cargo feature for this compression is not enabled.
+    pub(crate) fn check_supported(self) -> io::Result<()> {
+        match self {
+            #[cfg(not(feature = "gzip"))]
+            Compression::Gzip => Err(self.unsupported("gzip")),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(self.unsupported("zstd")),
+            _ => Ok(()),
+        }
+    }
+
+    /// The error for a compression whose cargo `feature` is not enabled.
+    #[cfg(not(all(feature = "gzip", feature = "zstd")))]
+    fn unsupported(self, feature: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{self:?} compression requires the `{feature}` feature of syt"),
+        )
+    }
+}
+
+/// A reader of a YAML file that decompresses it if needed.
+///
+/// This is the source used by the lazy iterators when they are created from a path, such as
+/// with [crate::lazy::LazyDocs::new].
+pub struct FileSource {
+    inner: Inner,
+}
+
+enum Inner {
+    Plain(File),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::read::MultiGzDecoder<File>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<File>>),
+}
+
+impl FileSource {
+    /// Opens the file at `path`, detecting its compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or is compressed with a format for which
+    /// the cargo feature is not enabled.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let inner = match Compression::detect_file(&mut file, path)? {
+            Compression::None => Inner::Plain(file),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Inner::Gzip(flate2::read::MultiGzDecoder::new(file)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Inner::Zstd(zstd::stream::read::Decoder::new(file)?),
+            #[cfg(not(feature = "gzip"))]
+            compression @ Compression::Gzip => return Err(compression.unsupported("gzip")),
+            #[cfg(not(feature = "zstd"))]
+            compression @ Compression::Zstd => return Err(compression.unsupported("zstd")),
+        };
+        Ok(FileSource { inner })
+    }
+
+    /// Reads an uncompressed file from its current position.
+    pub(crate) fn plain(file: File) -> Self {
+        FileSource {
+            inner: Inner::Plain(file),
+        }
+    }
+
+    /// Opens the file at `path` and wraps it in a [BufReader].
+    pub(crate) fn open_buffered(path: &Path) -> io::Result<BufReader<Self>> {
+        Ok(BufReader::new(Self::open(path)?))
+    }
+}
+
+impl Read for FileSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Plain(file) => file.read(buf),
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(decoder) => decoder.read(buf),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::append::append_or_new;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Record {
+        id: u32,
+    }
+
+    #[test]
+    fn test_detect() {
+        // GIVEN files with magic bytes and extensions
+        let dir = tempfile::tempdir().unwrap();
+        let gz = dir.path().join("a.yml");
+        std::fs::write(&gz, [0x1f, 0x8b, 0x08]).unwrap();
+        let zst = dir.path().join("b.yml.gz");
+        std::fs::write(&zst, [0x28, 0xb5, 0x2f, 0xfd]).unwrap();
+        let empty = dir.path().join("c.yml.zst");
+        std::fs::write(&empty, []).unwrap();
+
+        // WHEN detected
+        // THEN the magic bytes win over the extension
+        assert_eq!(Compression::detect(&gz).unwrap(), Compression::Gzip);
+        assert_eq!(Compression::detect(&zst).unwrap(), Compression::Zstd);
+
+        // THEN the extension is used for empty and missing files
+        assert_eq!(Compression::detect(&empty).unwrap(), Compression::Zstd);
+        assert_eq!(
+            Compression::detect(&dir.path().join("d.yml.gz")).unwrap(),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::detect(&dir.path().join("e.yml")).unwrap(),
+            Compression::None
+        );
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn append_and_read(path: std::path::PathBuf) {
+        // WHEN records are appended
+        append_or_new(&path, Record { id: 1 }).unwrap();
+        crate::append::append_or_new_with_comments(&path, Record { id: 2 }, |_| {
+            Some("comment".to_string())
+        })
+        .unwrap();
+        append_or_new(&path, Record { id: 3 }).unwrap();
+
+        // THEN they are read back lazily
+        let docs = crate::lazy::LazyDocs::<Record>::new(&path)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            docs,
+            vec![Record { id: 1 }, Record { id: 2 }, Record { id: 3 }]
+        );
+
+        // THEN the text is the same as an uncompressed file
+        let text = crate::lazy::LazyDocStart::new(&path)
+            .unwrap()
+            .collect::<String>();
+        assert_eq!(text, "id: 1\n\n---\n# comment\nid: 2\n\n---\nid: 3\n");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_append_and_read() {
+        // GIVEN a path to a gzip file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml.gz");
+
+        append_and_read(path.clone());
+
+        // THEN the file is gzip
+        assert_eq!(std::fs::read(&path).unwrap()[..2], *GZIP_MAGIC);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_append_and_read() {
+        // GIVEN a path to a zstd file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml.zst");
+
+        append_and_read(path.clone());
+
+        // THEN the file is zstd
+        assert_eq!(std::fs::read(&path).unwrap()[..4], *ZSTD_MAGIC);
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[test]
+    fn test_unlocked_concurrent_appends() {
+        use crate::append::{AppendOptions, Lock};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = Vec::new();
+        #[cfg(feature = "gzip")]
+        paths.push(dir.path().join("records.yml.gz"));
+        #[cfg(feature = "zstd")]
+        paths.push(dir.path().join("records.yml.zst"));
+        for path in paths {
+            // GIVEN a compressed file with a document
+            append_or_new(&path, Record { id: 0 }).unwrap();
+
+            // WHEN threads append to it without locking
+            let options = AppendOptions::new().lock(Lock::None);
+            std::thread::scope(|scope| {
+                for thread in 0..4 {
+                    let (path, options) = (&path, &options);
+                    scope.spawn(move || {
+                        for i in 1..=50 {
+                            let id = thread * 100 + i;
+                            options.append(path, Record { id }).unwrap();
+                        }
+                    });
+                }
+            });
+
+            // THEN every document reads back intact, as each is written in one piece
+            let mut ids = crate::lazy::LazyDocs::<Record>::new(&path)
+                .unwrap()
+                .try_iter()
+                .map(|doc| doc.unwrap().id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            let expected = (0..4).flat_map(|thread| (1..=50).map(move |i| thread * 100 + i));
+            assert_eq!(ids, std::iter::once(0).chain(expected).collect::<Vec<_>>());
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_detected_by_magic() {
+        // GIVEN a gzip file without a gzip extension
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+        let bytes = Compression::Gzip.encode(b"id: 1\n---\nid: 2\n").unwrap();
+        std::fs::write(&path, bytes).unwrap();
+
+        // WHEN read lazily
+        let docs = crate::lazy::LazyDocs::<Record>::new(&path)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        // THEN it is decompressed
+        assert_eq!(docs, vec![Record { id: 1 }, Record { id: 2 }]);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_without_feature() {
+        // GIVEN a gzip file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+        std::fs::write(&path, [0x1f, 0x8b, 0x08]).unwrap();
+
+        // WHEN read without the feature
+        let result = crate::lazy::LazyDocs::<Record>::new(&path);
+
+        // THEN it is an error
+        let Err(crate::Error::IoError(err)) = result else {
+            panic!("expected an I/O error");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_append_without_feature() {
+        // GIVEN a new file with a gzip extension and an existing gzip file
+        let dir = tempfile::tempdir().unwrap();
+        let new = dir.path().join("new.yml.gz");
+        let existing = dir.path().join("existing.yml");
+        std::fs::write(&existing, [0x1f, 0x8b, 0x08]).unwrap();
+
+        for path in [new, existing] {
+            // WHEN appended to without the feature
+            let result = append_or_new(&path, Record { id: 1 });
+
+            // THEN it is an error and nothing is written
+            let Err(crate::Error::IoError(err)) = result else {
+                panic!("expected an I/O error");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+            assert!(!std::fs::read(&path).unwrap().ends_with(b"id: 1\n"));
+        }
+    }
+}
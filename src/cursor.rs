@@ -0,0 +1,381 @@
+//! Provides a persistable position in a stream of YAML documents, so that a consumer can resume
+//! reading where it left off.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::compress::{Compression, FileSource};
+use crate::lazy::{LazyDocStart, LazyDocs};
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues a 64-bit FNV-1a hash with `bytes`.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The number of bytes before a position that a window checksum hashes.
+pub(crate) const CHECKSUM_WINDOW: u64 = 64 * 1024;
+
+/// The last [CHECKSUM_WINDOW] bytes of a stream, or all of them if there are fewer.
+#[derive(Debug, Default)]
+pub(crate) struct Window {
+    bytes: VecDeque<u8>,
+}
+
+impl Window {
+    /// Reads the window of `reader` before `end`, leaving `reader` positioned at `end`.
+    ///
+    /// Returns `None` if the reader has fewer than `end` bytes.
+    pub(crate) fn read<R: Read + Seek>(reader: &mut R, end: u64) -> io::Result<Option<Self>> {
+        let start = end.saturating_sub(CHECKSUM_WINDOW);
+        reader.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::with_capacity((end - start) as usize);
+        reader.by_ref().take(end - start).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != end - start {
+            return Ok(None);
+        }
+        Ok(Some(Window {
+            bytes: bytes.into(),
+        }))
+    }
+
+    /// Adds bytes that follow the window, dropping those that no longer fit.
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        let max = CHECKSUM_WINDOW as usize;
+        let bytes = &bytes[bytes.len().saturating_sub(max)..];
+        let excess = (self.bytes.len() + bytes.len()).saturating_sub(max);
+        self.bytes.drain(..excess);
+        self.bytes.extend(bytes);
+    }
+
+    /// The 64-bit FNV-1a hash of the window.
+    pub(crate) fn checksum(&self) -> u64 {
+        let (front, back) = self.bytes.as_slices();
+        fnv1a(fnv1a(FNV_OFFSET_BASIS, front), back)
+    }
+}
+
+/// Hashes the window of `reader` before `end`, leaving `reader` positioned at `end`.
+///
+/// Returns `None` if the reader has fewer than `end` bytes.
+pub(crate) fn window_checksum<R: Read + Seek>(reader: &mut R, end: u64) -> io::Result<Option<u64>> {
+    Ok(Window::read(reader, end)?.map(|window| window.checksum()))
+}
+
+/// The position of the next document to be read by a [LazyDocs] iterator.
+///
+/// A cursor can be saved, for example as YAML, and later passed to [LazyDocs::resume] to
+/// continue reading from the same document. It records checksums of the bytes before the
+/// position, so that resuming fails if the file has been rewritten rather than appended to: one
+/// of every byte, for sources that must be read from the start, and one of the last 64 KiB, for
+/// uncompressed files, which are resumed by seeking straight to the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The byte offset of the next document. For compressed files, this is the offset in the
+    /// decompressed text.
+    pub offset: u64,
+    /// The zero-based index of the next document.
+    pub index: usize,
+    /// The number of lines before the next document.
+    pub line: usize,
+    /// The 64-bit FNV-1a hash of the bytes before the next document.
+    pub checksum: u64,
+    /// The 64-bit FNV-1a hash of the 64 KiB before the next document, or all of them if there
+    /// are fewer.
+    pub window_checksum: u64,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor {
+            offset: 0,
+            index: 0,
+            line: 0,
+            checksum: FNV_OFFSET_BASIS,
+            window_checksum: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> LazyDocs<T, R> {
+    /// The position of the next document to be read.
+    ///
+    /// Iterators created from a [crate::index::DocIndex] start partway through the file without
+    /// reading what precedes it, so their cursors cannot be resumed.
+    pub fn cursor(&self) -> Cursor {
+        self.doc_start().cursor()
+    }
+
+    /// Creates a `LazyDocs` iterator that continues from `cursor` in the YAML documents read
+    /// from `reader`, which must be at the start of the stream.
+    ///
+    /// The bytes before the cursor are read to verify their checksum, but are not parsed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [crate::Error::StaleCursor] if the stream is shorter than the cursor or the bytes
+    /// before it have changed, or an error if they cannot be read.
+    pub fn resume_from_reader(mut reader: R, cursor: &Cursor) -> crate::Result<Self> {
+        let mut checksum = FNV_OFFSET_BASIS;
+        let mut window = Window::default();
+        let mut remaining = cursor.offset;
+        while remaining > 0 {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Err(crate::Error::StaleCursor);
+            }
+            let n = buf.len().min(remaining as usize);
+            checksum = fnv1a(checksum, &buf[..n]);
+            window.push(&buf[..n]);
+            reader.consume(n);
+            remaining -= n as u64;
+        }
+        if checksum != cursor.checksum {
+            return Err(crate::Error::StaleCursor);
+        }
+        Ok(Self::resumed(reader, cursor, window))
+    }
+
+    /// Creates a `LazyDocs` iterator for a reader positioned at `cursor`, the bytes before which
+    /// have been checked.
+    fn resumed(reader: R, cursor: &Cursor, window: Window) -> Self {
+        let doc_start =
+            LazyDocStart::from_reader_at(reader, cursor.index, cursor.line, cursor.offset)
+                .with_checksums(cursor.checksum, window);
+        Self::from_doc_start(doc_start)
+    }
+}
+
+impl<T: DeserializeOwned> LazyDocs<T, BufReader<FileSource>> {
+    /// Creates a `LazyDocs` iterator that continues from `cursor` in the YAML file at `path`.
+    ///
+    /// An uncompressed file is checked to be at least as long as the cursor and to have the same
+    /// 64 KiB before it, and read from there. A compressed file is read from the start as by
+    /// [LazyDocs::resume_from_reader].
+    ///
+    /// # Errors
+    ///
+    /// Returns [crate::Error::StaleCursor] if the file is shorter than the cursor or has changed
+    /// before it, or an error if it cannot be read.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use syt::append::append_or_new;
+    /// use syt::cursor::Cursor;
+    /// use syt::lazy::LazyDocs;
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    /// struct Job {
+    ///     id: u32,
+    /// }
+    ///
+    /// # fn main() -> Result<(), syt::Error> {
+    /// let dir = tempfile::tempdir()?;
+    /// let path = dir.path().join("jobs.yml");
+    /// append_or_new(&path, Job { id: 1 })?;
+    /// append_or_new(&path, Job { id: 2 })?;
+    ///
+    /// let mut jobs = LazyDocs::<Job>::new(&path)?;
+    /// assert_eq!(jobs.next(), Some(Job { id: 1 }));
+    /// let saved = serde_yml::to_string(&jobs.cursor())?;
+    ///
+    /// // later, after a restart
+    /// let cursor: Cursor = serde_yml::from_str(&saved)?;
+    /// let jobs = LazyDocs::<Job>::resume(&path, &cursor)?;
+    /// assert_eq!(jobs.collect::<Vec<_>>(), vec![Job { id: 2 }]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume(path: &Path, cursor: &Cursor) -> crate::Result<Self> {
+        let mut file = File::open(path)?;
+        if Compression::detect_file(&mut file, path)? != Compression::None {
+            return Self::resume_from_reader(FileSource::open_buffered(path)?, cursor);
+        }
+        let window = match Window::read(&mut file, cursor.offset)? {
+            Some(window) if window.checksum() == cursor.window_checksum => window,
+            _ => return Err(crate::Error::StaleCursor),
+        };
+        let reader = BufReader::new(FileSource::plain(file));
+        Ok(Self::resumed(reader, cursor, window))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor as IoCursor;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::append::append_or_new;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Job {
+        id: u32,
+    }
+
+    const YAML: &str = "# jobs\nid: 1\n---\n# empty\n---\nid: 2\n---\nid: 3\n";
+
+    #[test]
+    fn test_cursor_positions() {
+        // GIVEN documents with an empty one
+        let mut docs = LazyDocs::<Job, _>::from_str(YAML);
+        assert_eq!(docs.cursor(), Cursor::default());
+
+        // WHEN documents are read
+        docs.next();
+        let cursor = docs.cursor();
+
+        // THEN the cursor is at the start of the next document
+        assert_eq!((cursor.offset, cursor.index, cursor.line), (13, 1, 2));
+        assert_eq!(
+            cursor.checksum,
+            fnv1a(FNV_OFFSET_BASIS, &YAML.as_bytes()[..13])
+        );
+        assert_eq!(cursor.window_checksum, cursor.checksum);
+
+        docs.next();
+        let cursor = docs.cursor();
+        assert_eq!((cursor.offset, cursor.index, cursor.line), (35, 2, 6));
+        assert_eq!(
+            cursor.checksum,
+            fnv1a(FNV_OFFSET_BASIS, &YAML.as_bytes()[..35])
+        );
+
+        // THEN the cursor is at the end after the last document
+        docs.next();
+        assert_eq!(docs.next(), None);
+        assert_eq!(docs.cursor().offset, YAML.len() as u64);
+    }
+
+    #[test]
+    fn test_resume_each_position() {
+        // GIVEN the cursor before each document
+        let mut docs = LazyDocs::<Job, _>::from_str(YAML);
+        let mut cursors = vec![docs.cursor()];
+        while docs.next().is_some() {
+            cursors.push(docs.cursor());
+        }
+
+        for (i, cursor) in cursors.iter().enumerate() {
+            // WHEN resumed from it
+            let docs = LazyDocs::<Job, _>::resume_from_reader(IoCursor::new(YAML), cursor)
+                .unwrap()
+                .try_iter()
+                .collect::<Vec<_>>();
+
+            // THEN the remaining documents are read
+            assert_eq!(docs.len(), 3 - i);
+            for (doc, id) in docs.iter().zip(i as u32 + 1..) {
+                assert_eq!(doc.as_ref().unwrap(), &Job { id });
+            }
+        }
+    }
+
+    #[test]
+    fn test_resume_appended_file() {
+        // GIVEN a cursor at the end of a file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.yml");
+        append_or_new(&path, Job { id: 1 }).unwrap();
+        let mut docs = LazyDocs::<Job>::new(&path).unwrap();
+        docs.next();
+        let cursor = docs.cursor();
+
+        // WHEN the file is appended to and resumed
+        append_or_new(&path, Job { id: 2 }).unwrap();
+        let docs = LazyDocs::<Job>::resume(&path, &cursor)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        // THEN only the new document is read
+        assert_eq!(docs, vec![Job { id: 2 }]);
+    }
+
+    #[test]
+    fn test_resume_large_file() {
+        // GIVEN a cursor further into a file than the checksum window
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.yml");
+        let yaml = (0..10_000)
+            .map(|id| format!("---\nid: {id}\n"))
+            .collect::<String>();
+        std::fs::write(&path, &yaml).unwrap();
+        let mut docs = LazyDocs::<Job>::new(&path).unwrap();
+        docs.by_ref().take(9_000).for_each(drop);
+        let cursor = docs.cursor();
+        assert!(cursor.offset > CHECKSUM_WINDOW);
+
+        // THEN the window checksum is of the bytes just before it
+        let end = cursor.offset as usize;
+        let window = &yaml.as_bytes()[end - CHECKSUM_WINDOW as usize..end];
+        assert_eq!(cursor.window_checksum, fnv1a(FNV_OFFSET_BASIS, window));
+
+        // WHEN resumed by seeking in the file and by reading from the start
+        let mut seeked = LazyDocs::<Job>::resume(&path, &cursor).unwrap();
+        let mut read = LazyDocs::<Job, _>::resume_from_reader(yaml.as_bytes(), &cursor).unwrap();
+
+        // THEN both continue with the next document and the same cursors
+        let next = docs.next();
+        assert_eq!(seeked.next(), next);
+        assert_eq!(read.next(), next);
+        assert_eq!(seeked.cursor(), docs.cursor());
+        assert_eq!(read.cursor(), docs.cursor());
+        assert_eq!(seeked.count(), 999);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_resume_compressed_file() {
+        // GIVEN a cursor partway through a gzip file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.yml.gz");
+        append_or_new(&path, Job { id: 1 }).unwrap();
+        append_or_new(&path, Job { id: 2 }).unwrap();
+        let mut docs = LazyDocs::<Job>::new(&path).unwrap();
+        docs.next();
+
+        // WHEN resumed
+        let docs = LazyDocs::<Job>::resume(&path, &docs.cursor())
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        // THEN it is read from the start and the rest of the documents yielded
+        assert_eq!(docs, vec![Job { id: 2 }]);
+    }
+
+    #[test]
+    fn test_resume_rewritten_file() {
+        // GIVEN a cursor partway through a file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.yml");
+        std::fs::write(&path, YAML).unwrap();
+        let mut docs = LazyDocs::<Job>::new(&path).unwrap();
+        docs.next();
+        let cursor = docs.cursor();
+
+        // WHEN the file is rewritten with the same length
+        std::fs::write(&path, YAML.replace("id: 1", "id: 9")).unwrap();
+        let rewritten = LazyDocs::<Job>::resume(&path, &cursor);
+
+        // THEN resuming fails
+        assert!(matches!(rewritten, Err(crate::Error::StaleCursor)));
+
+        // WHEN the file is truncated
+        std::fs::write(&path, "id: 1\n").unwrap();
+        let truncated = LazyDocs::<Job>::resume(&path, &cursor);
+
+        // THEN resuming fails
+        assert!(matches!(truncated, Err(crate::Error::StaleCursor)));
+    }
+}
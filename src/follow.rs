@@ -0,0 +1,571 @@
+//! Provides an iterator that follows a YAML file as documents are appended to it.
+use std::collections::VecDeque;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use crate::boundary::DocBounds;
+use crate::lazy::RawDoc;
+
+/// An iterator that follows a YAML file like `tail -f`, created with [crate::lazy::LazyDocs::follow].
+///
+/// The documents already in the file are yielded first. When the end of the file is reached the
+/// iterator polls the file, blocking the calling thread, and yields each new document once it has
+/// been completely written. The iterator never ends.
+///
+/// A document is known to be complete when the next document starts, or when the file is rotated
+/// or truncated. The last document in the file is also yielded once it ends with a new line, can
+/// be deserialized, and the file has not grown for a whole poll interval, as
+/// [crate::append::append_or_new] writes each document in a single call. See
+/// [FollowDocs::yield_when_idle] to wait for the next document to start instead.
+///
+/// If the file is truncated, the last document read is yielded and the iterator starts again from
+/// the beginning of the file. If the file is rotated (renamed and replaced with a new file at the
+/// same path), the rest of the old file is read and yielded, and then the new file is followed
+/// from its beginning. Document indexes and line numbers in errors are relative to the file being
+/// followed.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use serde::{Deserialize, Serialize};
+/// use syt::append::append_or_new;
+/// use syt::lazy::LazyDocs;
+/// use syt::Error;
+/// use tempfile::NamedTempFile;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// struct Event {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let file = NamedTempFile::new()?;
+/// let path = file.path().to_path_buf();
+/// append_or_new(&path, Event { id: 1 })?;
+///
+/// let writer_path = path.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(50));
+///     append_or_new(&writer_path, Event { id: 2 }).unwrap();
+///     append_or_new(&writer_path, Event { id: 3 }).unwrap();
+/// });
+///
+/// let mut events = LazyDocs::<Event>::follow(&path)?.poll_interval(Duration::from_millis(10));
+/// assert_eq!(events.next().unwrap()?, Event { id: 1 });
+/// assert_eq!(events.next().unwrap()?, Event { id: 2 });
+/// # Ok(())
+/// # }
+/// ```
+pub struct FollowDocs<T: DeserializeOwned> {
+    path: PathBuf,
+    reader: BufReader<File>,
+    id: Option<(u64, u64)>,
+    poll_interval: Duration,
+    bounds: DocBounds,
+    /// A line that has not been terminated yet.
+    partial: String,
+    /// The text of the current document.
+    text: String,
+    /// Nothing has been read since the last poll.
+    idle: bool,
+    yield_when_idle: bool,
+    /// Documents read from a rotated file that have not been yielded yet.
+    pending: VecDeque<crate::Result<T>>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> FollowDocs<T> {
+    /// The default time to wait between polls of the file.
+    pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub(crate) fn new(path: &Path) -> crate::Result<Self> {
+        let file = File::open(path)?;
+        let id = file_id(&file.metadata()?);
+        Ok(FollowDocs {
+            path: path.to_path_buf(),
+            reader: BufReader::new(file),
+            id,
+            poll_interval: Self::DEFAULT_POLL_INTERVAL,
+            bounds: DocBounds::default(),
+            partial: String::new(),
+            text: String::new(),
+            idle: false,
+            yield_when_idle: true,
+            pending: VecDeque::new(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets the time to wait between polls of the file when the end of the file is reached.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets whether the last document in the file is yielded once it ends with a new line, can
+    /// be deserialized, and the file has not grown for a whole poll interval. This is on by
+    /// default.
+    ///
+    /// Files written with [crate::append::append_or_new] get each document in a single call, so
+    /// their last document is complete. A writer that pauses at the end of a line partway
+    /// through a document splits it in two: for example, if `a: 1` of `{a: 1, b: 2}` has been
+    /// written and `T` has no required `b`, the document `{a: 1}` is yielded and `b: 2` is read
+    /// as a separate document. For such writers, turn this off to yield a document only once the
+    /// next one starts.
+    pub fn yield_when_idle(mut self, yield_when_idle: bool) -> Self {
+        self.yield_when_idle = yield_when_idle;
+        self
+    }
+
+    /// Reads lines until a document is complete, returning `Ok(None)` at the end of the file.
+    fn read_doc(&mut self) -> io::Result<Option<crate::Result<T>>> {
+        loop {
+            let n = self.reader.read_line(&mut self.partial)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.idle = false;
+            if self.partial.ends_with('\n') {
+                let line = std::mem::take(&mut self.partial);
+                if let Some(doc) = self.push_line(line) {
+                    return Ok(Some(doc));
+                }
+            }
+        }
+    }
+
+    /// Accounts for a complete line, returning the previous document if the line starts a new one.
+    fn push_line(&mut self, line: String) -> Option<crate::Result<T>> {
+        let span = self.bounds.push(&line);
+        let doc = span.and_then(|span| span.raw(std::mem::take(&mut self.text)));
+        self.text.push_str(&line);
+        doc.map(|raw| parse(&raw))
+    }
+
+    /// The number of bytes read from the file.
+    fn offset(&self) -> u64 {
+        self.bounds.offset() + self.partial.len() as u64
+    }
+
+    fn at_eof(&mut self) -> Option<crate::Result<T>> {
+        let len = match fs::metadata(&self.path) {
+            Ok(metadata) => {
+                let rotated = file_id(&metadata) != self.id;
+                if rotated || metadata.len() < self.offset() {
+                    return self.reopen(rotated);
+                }
+                Some(metadata.len())
+            }
+            Err(_) => None,
+        };
+        if self.yield_when_idle
+            && self.idle
+            && len == Some(self.offset())
+            && self.partial.is_empty()
+            && self.bounds.has_content()
+        {
+            // the file has not grown for a whole poll interval
+            if let Ok(t) = serde_yml::from_str::<T>(&self.text) {
+                self.bounds.finish();
+                self.text.clear();
+                return Some(Ok(t));
+            }
+        }
+        self.idle = true;
+        thread::sleep(self.poll_interval);
+        None
+    }
+
+    /// Starts following the file at the path from its beginning.
+    ///
+    /// The last document of the old file is yielded first. If the file was rotated, the rest of
+    /// the old file is read first, as it may have been appended to since the end was reached.
+    fn reopen(&mut self, rotated: bool) -> Option<crate::Result<T>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) => return Some(Err(err.into())),
+        };
+        let id = match file.metadata() {
+            Ok(metadata) => file_id(&metadata),
+            Err(err) => return Some(Err(err.into())),
+        };
+        if rotated {
+            loop {
+                match self.read_doc() {
+                    Ok(Some(doc)) => self.pending.push_back(doc),
+                    Ok(None) => break,
+                    Err(err) => {
+                        self.pending.push_back(Err(err.into()));
+                        break;
+                    }
+                }
+            }
+        }
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            if let Some(doc) = self.push_line(line) {
+                self.pending.push_back(doc);
+            }
+        }
+        let text = std::mem::take(&mut self.text);
+        if let Some(raw) = self.bounds.finish().raw(text) {
+            self.pending.push_back(parse(&raw));
+        }
+        *self = FollowDocs {
+            path: std::mem::take(&mut self.path),
+            reader: BufReader::new(file),
+            id,
+            poll_interval: self.poll_interval,
+            bounds: DocBounds::default(),
+            partial: String::new(),
+            text: String::new(),
+            idle: false,
+            yield_when_idle: self.yield_when_idle,
+            pending: std::mem::take(&mut self.pending),
+            phantom: PhantomData,
+        };
+        self.pending.pop_front()
+    }
+
+    /// Reads the next document, polling the file at most once if none is ready.
+    fn poll(&mut self) -> Option<crate::Result<T>> {
+        if let Some(doc) = self.pending.pop_front() {
+            return Some(doc);
+        }
+        match self.read_doc() {
+            Ok(Some(doc)) => Some(doc),
+            Ok(None) => self.at_eof(),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for FollowDocs<T> {
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(doc) = self.poll() {
+                return Some(doc);
+            }
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(raw: &RawDoc) -> crate::Result<T> {
+    serde_yml::from_str::<T>(&raw.text).map_err(|err| raw.error(err))
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
+    use serde::{Deserialize, Serialize};
+    use serde_yml::Value;
+
+    use super::*;
+    use crate::append::append_or_new;
+    use crate::lazy::LazyDocs;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Event {
+        id: u32,
+        name: String,
+    }
+
+    fn event(id: u32) -> Event {
+        Event {
+            id,
+            name: format!("event {id}"),
+        }
+    }
+
+    /// Follows a file on another thread so that a broken test fails instead of hanging. The
+    /// thread stops when this is dropped.
+    struct Follower<T> {
+        rx: Receiver<crate::Result<T>>,
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl<T> Follower<T> {
+        fn recv_timeout(&self, timeout: Duration) -> Result<crate::Result<T>, RecvTimeoutError> {
+            self.rx.recv_timeout(timeout)
+        }
+    }
+
+    impl<T> Drop for Follower<T> {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn follow(path: &Path) -> Follower<Event> {
+        spawn(LazyDocs::<Event>::follow(path).unwrap())
+    }
+
+    fn spawn<T>(docs: FollowDocs<T>) -> Follower<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let mut docs = docs.poll_interval(Duration::from_millis(10));
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                if let Some(doc) = docs.poll() {
+                    if tx.send(doc).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Follower {
+            rx,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn recv(rx: &Follower<Event>) -> Event {
+        rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap()
+    }
+
+    fn append_raw(path: &Path, s: &str) {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .unwrap();
+        file.write_all(s.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_follow_appended_documents() {
+        // GIVEN a file with a document
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+
+        // WHEN following the file
+        let rx = follow(&path);
+
+        // THEN the existing document is yielded
+        assert_eq!(recv(&rx), event(1));
+
+        // WHEN documents are appended
+        append_or_new(&path, event(2)).unwrap();
+        append_or_new(&path, event(3)).unwrap();
+
+        // THEN they are yielded, including the last, and nothing else
+        assert_eq!(recv(&rx), event(2));
+        assert_eq!(recv(&rx), event(3));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_follow_partially_written_document() {
+        // GIVEN a file with a document and the start of another
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+        append_raw(&path, "\n---\nid: 2\nna");
+
+        // WHEN following the file
+        let rx = follow(&path);
+        assert_eq!(recv(&rx), event(1));
+
+        // THEN the partial document is not yielded
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // WHEN the document is finished and the next one started
+        append_raw(&path, "me: event 2\n---\n");
+
+        // THEN the document is yielded
+        assert_eq!(recv(&rx), event(2));
+    }
+
+    #[test]
+    fn test_follow_without_yield_when_idle() {
+        // GIVEN a file with the first line of a document
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("values.yml");
+        append_raw(&path, "a: 1\n");
+
+        // WHEN following the file, only yielding documents once the next one starts
+        let rx = spawn(
+            LazyDocs::<Value>::follow(&path)
+                .unwrap()
+                .yield_when_idle(false),
+        );
+
+        // THEN nothing is yielded while the writer pauses
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // WHEN the rest of the document and the next one are written
+        append_raw(&path, "b: 2\n---\nc: 3\n");
+
+        // THEN the whole document is yielded
+        let doc = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(doc, serde_yml::from_str::<Value>("{a: 1, b: 2}").unwrap());
+    }
+
+    #[test]
+    fn test_follow_yield_when_idle() {
+        // GIVEN a file with a document and the first line of another
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("values.yml");
+        append_raw(&path, "a: 1\n");
+
+        // WHEN following the file
+        let rx = spawn(LazyDocs::<Value>::follow(&path).unwrap());
+
+        // THEN the document is yielded without waiting for the next one, even if unfinished
+        let doc = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(doc, serde_yml::from_str::<Value>("{a: 1}").unwrap());
+
+        // WHEN the rest of the document is written
+        append_raw(&path, "b: 2\n");
+
+        // THEN it is yielded as a separate document
+        let doc = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(doc, serde_yml::from_str::<Value>("{b: 2}").unwrap());
+    }
+
+    #[test]
+    fn test_follow_document_missing_fields_is_not_yielded() {
+        // GIVEN a file where the last document is missing a field
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_raw(&path, "id: 1\n");
+
+        // WHEN following the file
+        let rx = follow(&path);
+
+        // THEN nothing is yielded
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // WHEN the rest of the document is written
+        append_raw(&path, "name: event 1\n");
+
+        // THEN the document is yielded
+        assert_eq!(recv(&rx), event(1));
+    }
+
+    #[test]
+    fn test_follow_truncated_file() {
+        // GIVEN a followed file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+        append_or_new(&path, event(2)).unwrap();
+        let rx = follow(&path);
+        assert_eq!(recv(&rx), event(1));
+
+        // WHEN the file is truncated and a document written
+        File::create(&path).unwrap();
+        append_or_new(&path, event(3)).unwrap();
+
+        // THEN the last document of the old contents is yielded
+        assert_eq!(recv(&rx), event(2));
+
+        // WHEN another document is written
+        append_or_new(&path, event(4)).unwrap();
+
+        // THEN the new document before it is yielded
+        assert_eq!(recv(&rx), event(3));
+    }
+
+    #[test]
+    fn test_follow_rotated_file() {
+        // GIVEN a followed file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+        let rx = follow(&path);
+
+        // WHEN a document is written, the file rotated, and documents written to the new file
+        append_or_new(&path, event(2)).unwrap();
+        fs::rename(&path, dir.path().join("events.yml.1")).unwrap();
+        append_or_new(&path, event(3)).unwrap();
+        append_or_new(&path, event(4)).unwrap();
+
+        // THEN all the documents are yielded
+        assert_eq!(recv(&rx), event(1));
+        assert_eq!(recv(&rx), event(2));
+        assert_eq!(recv(&rx), event(3));
+    }
+
+    #[test]
+    fn test_follow_rotated_file_appended_after_end_reached() {
+        // GIVEN a follower that has read to the end of a file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_or_new(&path, event(1)).unwrap();
+        append_or_new(&path, event(2)).unwrap();
+        let mut docs = LazyDocs::<Event>::follow(&path)
+            .unwrap()
+            .poll_interval(Duration::from_millis(10));
+        assert_eq!(docs.read_doc().unwrap().unwrap().unwrap(), event(1));
+        assert!(docs.read_doc().unwrap().is_none());
+
+        // WHEN the old file is appended to before it is rotated
+        append_or_new(&path, event(3)).unwrap();
+        fs::rename(&path, dir.path().join("events.yml.1")).unwrap();
+        append_or_new(&path, event(4)).unwrap();
+        append_or_new(&path, event(5)).unwrap();
+
+        // THEN when the rotation is noticed, the rest of the old file is yielded before the new file
+        assert_eq!(docs.at_eof().unwrap().unwrap(), event(2));
+        assert_eq!(docs.next().unwrap().unwrap(), event(3));
+        assert_eq!(docs.next().unwrap().unwrap(), event(4));
+    }
+
+    #[test]
+    fn test_follow_reports_bad_document() {
+        // GIVEN a file with a bad document followed by a good one
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.yml");
+        append_raw(&path, "id: 1\n");
+        append_or_new(&path, event(2)).unwrap();
+        append_or_new(&path, event(3)).unwrap();
+
+        // WHEN following the file
+        let rx = follow(&path);
+
+        // THEN the bad document is reported and the good one yielded
+        let err = rx
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::DocumentError(e) if e.index == 0));
+        assert_eq!(recv(&rx), event(2));
+    }
+}
@@ -0,0 +1,385 @@
+//! Provides reading and writing of YAML front matter in Markdown and other text files.
+//!
+//! Front matter is a YAML document at the very start of a file, between a `---` line and a
+//! closing `---` or `...` line. Everything after the closing line is the body, which is returned
+//! and written back exactly as it was.
+//!
+//! ```text
+//! ---
+//! title: Use YAML for configuration
+//! status: accepted
+//! ---
+//! # Context
+//! ...
+//! ```
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+
+use crate::append::parent_dir;
+use crate::boundary::{marker_rest, trim_line_ending};
+use crate::comments::KeyData;
+
+/// Splits text into its raw front matter, without the `---` lines, and its body.
+///
+/// Text that does not start with a `---` line, or has no closing line, has no front matter and
+/// is all body.
+///
+/// Example:
+///
+/// ```rust
+/// use syt::front_matter::split;
+///
+/// assert_eq!(split("---\ntitle: Hi\n---\n# Hi\n"), (Some("title: Hi\n"), "# Hi\n"));
+/// assert_eq!(split("# Hi\n"), (None, "# Hi\n"));
+/// ```
+pub fn split(text: &str) -> (Option<&str>, &str) {
+    let mut lines = text.split_inclusive('\n');
+    match lines.next() {
+        Some(first) if is_fence(first, "---") => {
+            let start = first.len();
+            let mut end = start;
+            for line in lines {
+                if is_fence(line, "---") || is_fence(line, "...") {
+                    return (Some(&text[start..end]), &text[end + line.len()..]);
+                }
+                end += line.len();
+            }
+            (None, text)
+        }
+        _ => (None, text),
+    }
+}
+
+/// Deserializes the front matter of text into `T`, and returns it with the body.
+///
+/// # Errors
+///
+/// Returns an [crate::Error::DocumentError] with the lines of the front matter if it cannot be
+/// deserialized into `T`.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::front_matter;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Page {
+///     title: String,
+/// }
+///
+/// let text = "---\ntitle: Home\n---\nWelcome!\n";
+///
+/// let (page, body) = front_matter::from_str::<Page>(text).unwrap();
+///
+/// assert_eq!(page, Some(Page { title: "Home".to_string() }));
+/// assert_eq!(body, "Welcome!\n");
+/// ```
+pub fn from_str<T: DeserializeOwned>(text: &str) -> crate::Result<(Option<T>, &str)> {
+    match split(text) {
+        (Some(yaml), body) => match serde_yml::from_str(yaml) {
+            Ok(t) => Ok((Some(t), body)),
+            Err(error) => Err(crate::DocumentError {
+                index: 0,
+                start_line: 2,
+                end_line: 1 + yaml.lines().count(),
+                error,
+            }
+            .into()),
+        },
+        (None, body) => Ok((None, body)),
+    }
+}
+
+/// Reads the file at `path` and deserializes its front matter into `T`.
+///
+/// See [from_str].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or its front matter cannot be deserialized.
+pub fn read<T: DeserializeOwned>(path: &Path) -> crate::Result<(Option<T>, String)> {
+    let text = fs::read_to_string(path)?;
+    let (front, body) = from_str(&text)?;
+    Ok((front, body.to_string()))
+}
+
+/// Serializes `front` as front matter to a writer, followed by `body`.
+///
+/// # Errors
+///
+/// Returns an error if `front` cannot be serialized or the writer fails.
+pub fn to_writer<W: Write, T: Serialize>(writer: W, front: &T, body: &str) -> crate::Result<()> {
+    write_with(writer, body, |writer| {
+        serde_yml::to_writer(writer, front)?;
+        Ok(())
+    })
+}
+
+/// Serializes `front` as front matter with comments to a writer, followed by `body`.
+///
+/// See [crate::comments::to_writer] for the callback.
+///
+/// # Errors
+///
+/// Returns an error if `front` cannot be serialized or the writer fails.
+pub fn to_writer_with_comments<W, T, F>(
+    writer: W,
+    front: &T,
+    body: &str,
+    cb: F,
+) -> crate::Result<()>
+where
+    W: Write,
+    T: Serialize,
+    F: Fn(KeyData) -> Option<String>,
+{
+    write_with(writer, body, |writer| {
+        crate::comments::to_writer(writer, front, cb)
+    })
+}
+
+/// Replaces the front matter of text with `front`, or inserts it if there is none.
+///
+/// The body is kept byte for byte.
+///
+/// # Errors
+///
+/// Returns an error if `front` cannot be serialized.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Serialize;
+/// use syt::front_matter;
+///
+/// #[derive(Serialize)]
+/// struct Page {
+///     title: String,
+/// }
+///
+/// let page = Page {
+///     title: "About".to_string(),
+/// };
+///
+/// let text = front_matter::replace("---\ntitle: Old\n---\nBody\r\n", &page).unwrap();
+/// assert_eq!(text, "---\ntitle: About\n---\nBody\r\n");
+///
+/// let text = front_matter::replace("Body\n", &page).unwrap();
+/// assert_eq!(text, "---\ntitle: About\n---\nBody\n");
+/// ```
+pub fn replace<T: Serialize>(text: &str, front: &T) -> crate::Result<String> {
+    let mut buf = Vec::new();
+    to_writer(&mut buf, front, split(text).1)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Replaces the front matter of the file at `path` with `front`, or inserts it if there is
+/// none, keeping the body byte for byte. The file is created if it does not exist.
+///
+/// The new text is written to a temporary file in the same directory, which then replaces the
+/// file, so that a crash or error part way through never leaves a truncated file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written, or `front` cannot be serialized.
+pub fn write<T: Serialize>(path: &Path, front: &T) -> crate::Result<()> {
+    rewrite(path, |text, buf| to_writer(buf, front, split(text).1))
+}
+
+/// Replaces the front matter of the file at `path` with `front` and comments from `cb`.
+///
+/// See [write()] and [crate::comments::to_writer].
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written, or `front` cannot be serialized.
+pub fn write_with_comments<T, F>(path: &Path, front: &T, cb: F) -> crate::Result<()>
+where
+    T: Serialize,
+    F: Fn(KeyData) -> Option<String>,
+{
+    rewrite(path, |text, buf| {
+        to_writer_with_comments(buf, front, split(text).1, cb)
+    })
+}
+
+/// Rewrites a file, which is empty if it does not exist, from its text.
+///
+/// The file is replaced by a temporary file with the same permissions.
+fn rewrite<F>(path: &Path, f: F) -> crate::Result<()>
+where
+    F: FnOnce(&str, &mut Vec<u8>) -> crate::Result<()>,
+{
+    let (text, permissions) = match fs::read_to_string(path) {
+        Ok(text) => (text, Some(fs::metadata(path)?.permissions())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (String::new(), None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut buf = Vec::new();
+    f(&text, &mut buf)?;
+    let mut file = NamedTempFile::new_in(parent_dir(path))?;
+    file.write_all(&buf)?;
+    if let Some(permissions) = permissions {
+        file.as_file().set_permissions(permissions)?;
+    }
+    file.persist(path).map_err(io::Error::from)?;
+    Ok(())
+}
+
+fn write_with<W, F>(mut writer: W, body: &str, write_front: F) -> crate::Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut W) -> crate::Result<()>,
+{
+    writer.write_all(b"---\n")?;
+    write_front(&mut writer)?;
+    writer.write_all(b"---\n")?;
+    writer.write_all(body.as_bytes())?;
+    Ok(())
+}
+
+/// Whether a line is a `---` or `...` fence with nothing else on it.
+fn is_fence(line: &str, marker: &str) -> bool {
+    marker_rest(trim_line_ending(line), marker).is_some_and(|rest| rest.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Adr {
+        title: String,
+        status: String,
+    }
+
+    fn adr(status: &str) -> Adr {
+        Adr {
+            title: "Use YAML".to_string(),
+            status: status.to_string(),
+        }
+    }
+
+    const BODY: &str = "# Context\r\n\r\n---\n\nA rule, not front matter.\n...\nno newline";
+
+    #[test]
+    fn test_split() {
+        // GIVEN front matter closed with `...` and a body with markers of its own
+        let text = format!("---\ntitle: Use YAML\n...\n{BODY}");
+
+        // WHEN split
+        let (front, body) = split(&text);
+
+        // THEN the first closing line ends the front matter
+        assert_eq!(front, Some("title: Use YAML\n"));
+        assert_eq!(body, BODY);
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        // GIVEN text that does not start with a fence, or is not closed
+        for text in [
+            "",
+            "# Title\n---\na: 1\n---\n",
+            "--- a\n---\n",
+            "---\na: 1\n",
+        ] {
+            // WHEN split
+            // THEN it is all body
+            assert_eq!(split(text), (None, text));
+        }
+    }
+
+    #[test]
+    fn test_empty_front_matter() {
+        // GIVEN front matter with nothing in it
+        // WHEN split
+        // THEN it is empty
+        assert_eq!(split("---\r\n---\r\nbody"), (Some(""), "body"));
+    }
+
+    #[test]
+    fn test_front_matter_error_lines() {
+        // GIVEN front matter that does not match the type
+        let text = "---\ntitle: Use YAML\nstatus: [accepted\n---\nbody\n";
+
+        // WHEN deserialized
+        let result = from_str::<Adr>(text);
+
+        // THEN the error has the lines of the front matter
+        let Err(crate::Error::DocumentError(err)) = result else {
+            panic!("expected a document error");
+        };
+        assert_eq!((err.index, err.start_line, err.end_line), (0, 2, 3));
+    }
+
+    #[test]
+    fn test_write_and_read_file() {
+        // GIVEN a file without front matter
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0001-use-yaml.md");
+        fs::write(&path, BODY).unwrap();
+
+        // WHEN front matter is inserted and then replaced
+        write(&path, &adr("proposed")).unwrap();
+        write(&path, &adr("accepted")).unwrap();
+
+        // THEN the file has the latest front matter and the body is unchanged
+        let (front, body) = read::<Adr>(&path).unwrap();
+        assert_eq!(front, Some(adr("accepted")));
+        assert_eq!(body, BODY);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("---\ntitle: Use YAML\nstatus: accepted\n---\n{BODY}")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_replaces_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // GIVEN a file with front matter and its own permissions
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0001-use-yaml.md");
+        write(&path, &adr("proposed")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        // WHEN the front matter is replaced
+        write(&path, &adr("accepted")).unwrap();
+
+        // THEN the file keeps its permissions and no temporary file is left behind
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_new_file_with_comments() {
+        // GIVEN a file that does not exist
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.md");
+
+        // WHEN front matter is written with comments
+        write_with_comments(&path, &adr("proposed"), |key| {
+            (key.str == "status").then(|| "proposed, accepted or superseded".to_string())
+        })
+        .unwrap();
+
+        // THEN the file has only the commented front matter
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            "---\ntitle: Use YAML\n# proposed, accepted or superseded\nstatus: proposed\n---\n"
+        );
+        assert_eq!(from_str::<Adr>(&text).unwrap(), (Some(adr("proposed")), ""));
+    }
+}
@@ -0,0 +1,175 @@
+//! Provides reading of YAML files whose first document is a header describing the rest.
+//!
+//! See [crate::append::append_or_new_with_header] for writing them.
+use std::io::BufRead;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use crate::lazy::LazyDocs;
+
+impl<T: DeserializeOwned> LazyDocs<T> {
+    /// Reads the first document of the YAML file at `path` as a header of type `H`, and returns
+    /// it with a `LazyDocs` iterator over the remaining documents.
+    ///
+    /// See [LazyDocs::split_header].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the YAML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, has no documents, or the first document
+    /// cannot be deserialized into `H`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use syt::append::append_or_new_with_header;
+    /// use syt::lazy::LazyDocs;
+    /// use syt::Error;
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    /// struct Header {
+    ///     schema: u32,
+    /// }
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    /// struct Record {
+    ///     id: u32,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let dir = tempfile::tempdir()?;
+    /// let path = dir.path().join("records.yml");
+    /// append_or_new_with_header(&path, Header { schema: 2 }, Record { id: 1 })?;
+    /// append_or_new_with_header(&path, Header { schema: 2 }, Record { id: 2 })?;
+    ///
+    /// let (header, records) = LazyDocs::<Record>::with_header::<Header>(&path)?;
+    ///
+    /// assert_eq!(header, Header { schema: 2 });
+    /// assert_eq!(records.collect::<Vec<_>>(), vec![Record { id: 1 }, Record { id: 2 }]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_header<H: DeserializeOwned>(path: &Path) -> crate::Result<(H, Self)> {
+        Self::new(path)?.split_header()
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> LazyDocs<T, R> {
+    /// Reads the next document as a header of type `H`, and returns it with this iterator over
+    /// the remaining documents.
+    ///
+    /// Documents keep their index in the stream, so the first document after the header has
+    /// index 1 in a [crate::DocumentError].
+    ///
+    /// # Errors
+    ///
+    /// Returns [crate::Error::MissingHeader] if there are no documents, or an error if the
+    /// header cannot be read or deserialized into `H`.
+    pub fn split_header<H: DeserializeOwned>(mut self) -> crate::Result<(H, Self)> {
+        match self.try_next_as::<H>() {
+            Some(header) => Ok((header?, self)),
+            None => Err(crate::Error::MissingHeader),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::append::{append_or_new, append_or_new_with_header};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Header {
+        schema: u32,
+        producer: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Record {
+        id: u32,
+    }
+
+    fn header(schema: u32) -> Header {
+        Header {
+            schema,
+            producer: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_header_written_once() {
+        // GIVEN a new file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+
+        // WHEN records are appended with different headers
+        append_or_new_with_header(&path, header(1), Record { id: 1 }).unwrap();
+        append_or_new_with_header(&path, header(2), Record { id: 2 }).unwrap();
+
+        // THEN only the first header is written
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            "schema: 1\nproducer: test\n\n---\nid: 1\n\n---\nid: 2\n"
+        );
+        let (actual, records) = LazyDocs::<Record>::with_header::<Header>(&path).unwrap();
+        assert_eq!(actual, header(1));
+        assert_eq!(
+            records.collect::<Vec<_>>(),
+            vec![Record { id: 1 }, Record { id: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_header_not_written_to_existing_file() {
+        // GIVEN an existing file without a header
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+        append_or_new(&path, Record { id: 1 }).unwrap();
+
+        // WHEN a record is appended with a header
+        append_or_new_with_header(&path, header(1), Record { id: 2 }).unwrap();
+
+        // THEN the file is left alone and the first document is not a header
+        let result = LazyDocs::<Record>::with_header::<Header>(&path);
+        assert!(matches!(result, Err(crate::Error::DocumentError(_))));
+    }
+
+    #[test]
+    fn test_missing_header() {
+        // GIVEN no documents
+        let docs = LazyDocs::<Record, _>::from_str("# nothing\n");
+
+        // WHEN the header is split
+        let result = docs.split_header::<Header>();
+
+        // THEN it is missing
+        assert!(matches!(result, Err(crate::Error::MissingHeader)));
+    }
+
+    #[test]
+    fn test_split_header_keeps_indexes() {
+        // GIVEN a header and a bad record
+        let yaml = "schema: 1\nproducer: test\n---\nid: 1\n---\nname: no id\n";
+
+        // WHEN the header is split and the records are read
+        let (_, records) = LazyDocs::<Record, _>::from_str(yaml)
+            .split_header::<Header>()
+            .unwrap();
+        let records = records.try_iter().collect::<Vec<_>>();
+
+        // THEN the records keep their index in the stream
+        assert_eq!(records[0].as_ref().unwrap(), &Record { id: 1 });
+        let Err(crate::Error::DocumentError(err)) = &records[1] else {
+            panic!("expected a document error");
+        };
+        assert_eq!(err.index, 2);
+    }
+}
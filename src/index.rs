@@ -0,0 +1,476 @@
+//! Provides an index of the documents in a YAML file for random access.
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use tempfile::NamedTempFile;
+
+use crate::append::parent_dir;
+use crate::compress::Compression;
+use crate::cursor::window_checksum;
+use crate::lazy::{LazyDocStart, LazyDocs};
+
+/// The location of a document in a YAML file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocEntry {
+    /// The byte offset at which the document starts.
+    pub offset: u64,
+    /// The one-based line on which the document starts.
+    pub line: usize,
+}
+
+/// An index of the documents in a multi-document YAML file.
+///
+/// The index records where each document starts, so that a [LazyDocs] iterator can seek
+/// straight to document N without scanning the file from the beginning. Document numbering is
+/// the same as that of [LazyDocs] and [crate::DocumentError].
+///
+/// The index remembers how much of the file it has scanned, along with a checksum of the last
+/// bytes scanned. When the file grows, such as when documents are added with
+/// [crate::append::append_or_new], [DocIndex::update] scans only the new part of the file. If the
+/// file shrinks, or the bytes before the end of the scan have changed, the index is rebuilt.
+///
+/// The index can be persisted to a sidecar file, see [DocIndex::open].
+///
+/// Offsets are positions in the file itself, so compressed files cannot be indexed: indexing
+/// one fails with an [io::ErrorKind::Unsupported] error.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use syt::append::append_or_new;
+/// use syt::index::DocIndex;
+/// use syt::Error;
+/// use tempfile::NamedTempFile;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// struct Record {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let file = NamedTempFile::new()?;
+/// let path = file.path();
+/// for id in 0..10 {
+///     append_or_new(path, Record { id })?;
+/// }
+///
+/// let mut index = DocIndex::build(path)?;
+/// assert_eq!(index.len(), 10);
+///
+/// let mut docs = index.docs_from::<Record>(path, 7)?;
+/// assert_eq!(docs.next(), Some(Record { id: 7 }));
+///
+/// let docs = index.docs_range::<Record>(path, 2..4)?.collect::<Vec<_>>();
+/// assert_eq!(docs, vec![Record { id: 2 }, Record { id: 3 }]);
+///
+/// append_or_new(path, Record { id: 10 })?;
+/// index.update(path)?;
+/// assert_eq!(index.len(), 11);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocIndex {
+    /// The number of bytes of the file that have been scanned.
+    scanned: u64,
+    /// The checksum of the last bytes scanned, see [crate::cursor::window_checksum].
+    checksum: u64,
+    entries: Vec<DocEntry>,
+}
+
+impl DocIndex {
+    /// Builds an index by scanning the YAML file at `path` once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is compressed.
+    pub fn build(path: &Path) -> crate::Result<Self> {
+        let mut index = DocIndex::default();
+        index.scan(path, 0)?;
+        Ok(index)
+    }
+
+    /// Opens the index kept in the sidecar file of the YAML file at `path`.
+    ///
+    /// The sidecar file is named after the YAML file with `.idx` appended (see
+    /// [DocIndex::sidecar_path]). If the sidecar does not exist or cannot be read, such as when it
+    /// was written by an older version of this crate, the index is built from scratch. The index is then brought up to date with the YAML file, and the
+    /// sidecar file is written if anything changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the YAML file cannot be read or the sidecar file cannot be written.
+    pub fn open(path: &Path) -> crate::Result<Self> {
+        let sidecar = Self::sidecar_path(path);
+        match Self::load(&sidecar) {
+            Ok(mut index) => {
+                if index.update(path)? {
+                    index.save(&sidecar)?;
+                }
+                Ok(index)
+            }
+            Err(_) => {
+                let index = Self::build(path)?;
+                index.save(&sidecar)?;
+                Ok(index)
+            }
+        }
+    }
+
+    /// Returns the path of the sidecar file for the YAML file at `path`.
+    pub fn sidecar_path(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".idx");
+        PathBuf::from(name)
+    }
+
+    /// Loads an index previously written with [DocIndex::save].
+    pub fn load(index_path: &Path) -> crate::Result<Self> {
+        let file = File::open(index_path)?;
+        Ok(serde_yml::from_reader(BufReader::new(file))?)
+    }
+
+    /// Writes the index to `index_path`.
+    ///
+    /// The index is written to a temporary file in the same directory, which then replaces
+    /// `index_path`, so that readers never see a partially written index.
+    pub fn save(&self, index_path: &Path) -> crate::Result<()> {
+        let mut file = NamedTempFile::new_in(parent_dir(index_path))?;
+        serde_yml::to_writer(&mut file, self)?;
+        file.persist(index_path).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// Brings the index up to date with the YAML file at `path`.
+    ///
+    /// If the file has grown, only the last indexed document and what follows it are scanned.
+    /// If the file has shrunk, or the bytes before the end of the last scan have changed, the
+    /// index is rebuilt.
+    ///
+    /// Returns `true` if the index changed.
+    pub fn update(&mut self, path: &Path) -> crate::Result<bool> {
+        let mut file = open_uncompressed(path)?;
+        let unchanged = window_checksum(&mut file, self.scanned)? == Some(self.checksum);
+        let len = file.metadata()?.len();
+        if unchanged && len == self.scanned {
+            return Ok(false);
+        }
+        let from = if unchanged {
+            // the last document may have been incomplete
+            self.entries.len().saturating_sub(1)
+        } else {
+            0
+        };
+        self.scan(path, from)?;
+        Ok(true)
+    }
+
+    /// The number of documents in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the index has no documents.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the location of document `n`.
+    pub fn get(&self, n: usize) -> Option<&DocEntry> {
+        self.entries.get(n)
+    }
+
+    /// Returns the locations of all the documents.
+    pub fn entries(&self) -> &[DocEntry] {
+        &self.entries
+    }
+
+    /// Creates a [LazyDocs] iterator that starts at document `n` of the YAML file at `path`.
+    ///
+    /// If `n` is beyond the end of the index, the iterator yields nothing.
+    pub fn docs_from<T: DeserializeOwned>(
+        &self,
+        path: &Path,
+        n: usize,
+    ) -> crate::Result<LazyDocs<T, BufReader<File>>> {
+        Ok(LazyDocs::from_doc_start(self.doc_start(path, n)?))
+    }
+
+    /// Creates a [LazyDocs] iterator over the documents of the YAML file at `path` in `range`.
+    pub fn docs_range<T: DeserializeOwned>(
+        &self,
+        path: &Path,
+        range: Range<usize>,
+    ) -> crate::Result<LazyDocs<T, BufReader<File>>> {
+        Ok(LazyDocs::from_doc_start(
+            self.doc_start(path, range.start)?.until(range.end),
+        ))
+    }
+
+    fn doc_start(&self, path: &Path, n: usize) -> crate::Result<LazyDocStart<BufReader<File>>> {
+        let mut file = open_uncompressed(path)?;
+        let doc_start = match self.entries.get(n) {
+            Some(entry) => {
+                file.seek(SeekFrom::Start(entry.offset))?;
+                LazyDocStart::from_reader_at(BufReader::new(file), n, entry.line - 1, entry.offset)
+            }
+            None => LazyDocStart::from_reader_at(BufReader::new(file), n, 0, 0).until(n),
+        };
+        Ok(doc_start)
+    }
+
+    /// Scans the file starting at the entry with index `from`, replacing it and any after it.
+    fn scan(&mut self, path: &Path, from: usize) -> crate::Result<()> {
+        let mut doc_start = if from == 0 {
+            LazyDocStart::from_reader(BufReader::new(open_uncompressed(path)?))
+        } else {
+            self.doc_start(path, from)?
+        };
+        self.entries.truncate(from);
+        while let Some(raw) = doc_start.next_raw() {
+            let raw = raw?;
+            self.entries.push(DocEntry {
+                offset: raw.offset,
+                line: raw.start_line,
+            });
+        }
+        self.scanned = doc_start.offset();
+        let mut file = File::open(path)?;
+        self.checksum = window_checksum(&mut file, self.scanned)?.unwrap_or_default();
+        Ok(())
+    }
+}
+
+/// Opens a file, failing if it is compressed.
+fn open_uncompressed(path: &Path) -> crate::Result<File> {
+    let mut file = File::open(path)?;
+    if Compression::detect_file(&mut file, path)? != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "compressed files cannot be indexed",
+        )
+        .into());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::append::append_or_new;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Record {
+        id: u32,
+    }
+
+    fn records(path: &Path, ids: Range<u32>) {
+        for id in ids {
+            append_or_new(path, Record { id }).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        // GIVEN a file with comments and documents with markers
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "# comment\nid: 0\n---\nid: 1\n--- \nid: 2\n").unwrap();
+
+        // WHEN the index is built
+        let index = DocIndex::build(file.path()).unwrap();
+
+        // THEN every document is located
+        assert_eq!(
+            index.entries(),
+            &[
+                DocEntry { offset: 0, line: 1 },
+                DocEntry {
+                    offset: 16,
+                    line: 3
+                },
+                DocEntry {
+                    offset: 26,
+                    line: 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_docs_from() {
+        // GIVEN an indexed file of records
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..100);
+        let index = DocIndex::build(file.path()).unwrap();
+
+        // WHEN reading from document 42
+        let docs = index
+            .docs_from::<Record>(file.path(), 42)
+            .unwrap()
+            .map(|r| r.id)
+            .collect::<Vec<_>>();
+
+        // THEN the documents from 42 on are read
+        assert_eq!(docs, (42..100).collect::<Vec<_>>());
+
+        // WHEN reading from beyond the end
+        let mut docs = index.docs_from::<Record>(file.path(), 100).unwrap();
+
+        // THEN nothing is read
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_docs_range_keeps_document_numbering() {
+        // GIVEN an indexed file where document 5 is bad
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..5);
+        append_or_new(file.path(), "bad").unwrap();
+        records(file.path(), 6..10);
+        let index = DocIndex::build(file.path()).unwrap();
+
+        // WHEN reading a range with try_iter
+        let docs = index
+            .docs_range::<Record>(file.path(), 4..7)
+            .unwrap()
+            .try_iter()
+            .collect::<Vec<_>>();
+
+        // THEN only the range is read and the error has the document index and line
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[0].as_ref().unwrap(), &Record { id: 4 });
+        let Err(crate::Error::DocumentError(err)) = &docs[1] else {
+            panic!("expected a document error");
+        };
+        assert_eq!(err.index, 5);
+        assert_eq!(err.start_line, index.get(5).unwrap().line);
+        assert_eq!(docs[2].as_ref().unwrap(), &Record { id: 6 });
+    }
+
+    #[test]
+    fn test_update_after_append() {
+        // GIVEN an indexed file
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..3);
+        let mut index = DocIndex::build(file.path()).unwrap();
+
+        // WHEN nothing changes
+        // THEN the index is not changed
+        assert!(!index.update(file.path()).unwrap());
+
+        // WHEN more records are appended and the index is updated
+        records(file.path(), 3..5);
+        assert!(index.update(file.path()).unwrap());
+
+        // THEN the index is the same as one built from scratch
+        assert_eq!(index, DocIndex::build(file.path()).unwrap());
+        assert_eq!(index.len(), 5);
+    }
+
+    #[test]
+    fn test_update_after_last_document_grows() {
+        // GIVEN an indexed file where the last document was partially written
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..2);
+        let mut f = OpenOptions::new().append(true).open(file.path()).unwrap();
+        write!(f, "\n---\nid: ").unwrap();
+        let mut index = DocIndex::build(file.path()).unwrap();
+        assert_eq!(index.len(), 3);
+
+        // WHEN the rest of the document is written and the index is updated
+        write!(f, "2\n---\nid: 3\n").unwrap();
+        index.update(file.path()).unwrap();
+
+        // THEN the index is the same as one built from scratch
+        assert_eq!(index, DocIndex::build(file.path()).unwrap());
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn test_update_after_truncate() {
+        // GIVEN an indexed file
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..5);
+        let mut index = DocIndex::build(file.path()).unwrap();
+
+        // WHEN the file is rewritten with fewer records
+        File::create(file.path()).unwrap();
+        records(file.path(), 0..2);
+        index.update(file.path()).unwrap();
+
+        // THEN the index is rebuilt
+        assert_eq!(index, DocIndex::build(file.path()).unwrap());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_update_after_rewrite() {
+        // GIVEN an indexed file
+        let file = NamedTempFile::new().unwrap();
+        records(file.path(), 0..3);
+        let mut index = DocIndex::build(file.path()).unwrap();
+
+        // WHEN the file is rewritten with a longer first record
+        File::create(file.path()).unwrap();
+        append_or_new(file.path(), "a much longer first record").unwrap();
+        records(file.path(), 1..3);
+        assert!(index.update(file.path()).unwrap());
+
+        // THEN the index is rebuilt
+        assert_eq!(index, DocIndex::build(file.path()).unwrap());
+        assert_eq!(index.len(), 3);
+    }
+
+    #[test]
+    fn test_open_sidecar() {
+        // GIVEN a file of records
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+        records(&path, 0..3);
+
+        // WHEN the index is opened
+        let index = DocIndex::open(&path).unwrap();
+
+        // THEN the sidecar file is written
+        let sidecar = DocIndex::sidecar_path(&path);
+        assert_eq!(sidecar, dir.path().join("records.yml.idx"));
+        assert_eq!(DocIndex::load(&sidecar).unwrap(), index);
+
+        // WHEN records are appended and the index is opened again
+        records(&path, 3..4);
+        let index = DocIndex::open(&path).unwrap();
+
+        // THEN the sidecar file is updated
+        assert_eq!(index.len(), 4);
+        assert_eq!(DocIndex::load(&sidecar).unwrap(), index);
+    }
+
+    #[test]
+    fn test_compressed_file() {
+        // GIVEN a file that starts with the gzip magic bytes
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.yml");
+        std::fs::write(&path, [0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00]).unwrap();
+
+        // WHEN it is indexed
+        let result = DocIndex::build(&path);
+
+        // THEN it is unsupported
+        let Err(crate::Error::IoError(err)) = result else {
+            panic!("expected an io error");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
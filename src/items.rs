@@ -0,0 +1,490 @@
+//! Provides lazy iterators over the elements of top-level sequences, or the entries of top-level
+//! mappings, in YAML documents too large to read into memory.
+//!
+//! [crate::lazy::LazyDocs] reads a whole document before deserializing it. [SeqItems] and
+//! [MapEntries] instead read one element or entry at a time, so only the largest element is
+//! ever held in memory. They work on block style documents, such as those written by
+//! [crate::append::append_or_new], where each element of a top-level sequence starts with a `-`
+//! at the start of a line and each entry of a top-level mapping starts with its key at the start
+//! of a line.
+//!
+//! Every element is deserialized on its own, so aliases cannot refer to anchors in other
+//! elements, and the elements of a flow sequence such as `[1, 2, 3]` are not split.
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::de::{self, DeserializeOwned, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+
+use crate::boundary::{marker_rest, strip_comment, trim_line_ending, DocBounds};
+use crate::compress::FileSource;
+use crate::lazy::RawDoc;
+
+/// A lazy iterator over the elements of the top-level sequences in YAML documents.
+///
+/// The elements of every document are yielded in turn, so a file built by appending batches
+/// with [crate::append::append_or_new] is read as one sequence. An element that cannot be
+/// deserialized yields a [crate::Error::DocumentError] with the index of its document and the
+/// lines of the element, and iteration continues. An I/O error ends the iteration.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use syt::append::append_or_new;
+/// use syt::items::SeqItems;
+///
+/// #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("points.yml");
+/// append_or_new(&path, vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }])?;
+/// append_or_new(&path, vec![Point { x: 5, y: 6 }])?;
+///
+/// let xs = SeqItems::<Point>::new(&path)?
+///     .map(|point| point.map(|point| point.x))
+///     .collect::<Result<Vec<_>, _>>()?;
+///
+/// assert_eq!(xs, vec![1, 3, 5]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SeqItems<T: DeserializeOwned, R: BufRead = BufReader<FileSource>> {
+    chunks: Chunks<R>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> SeqItems<T> {
+    /// Creates a new `SeqItems` iterator over the YAML file at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the YAML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn new(path: &Path) -> crate::Result<Self> {
+        Ok(Self::from_reader(FileSource::open_buffered(path)?))
+    }
+}
+
+impl<'a, T: DeserializeOwned> SeqItems<T, &'a [u8]> {
+    /// Creates a new `SeqItems` iterator over the YAML documents in a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Self {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> SeqItems<T, R> {
+    /// Creates a new `SeqItems` iterator over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R) -> Self {
+        SeqItems {
+            chunks: Chunks::new(reader, Collection::Sequence),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> Iterator for SeqItems<T, R> {
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let raw = match self.chunks.next_chunk()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err.into())),
+        };
+        // the element is read as a sequence of one
+        Some(
+            serde_yml::from_str::<(T,)>(&raw.text)
+                .map(|(t,)| t)
+                .map_err(|err| raw.error(err)),
+        )
+    }
+}
+
+/// A lazy iterator over the entries of the top-level mappings in YAML documents.
+///
+/// See [SeqItems].
+///
+/// Example:
+///
+/// ```rust
+/// use syt::items::MapEntries;
+///
+/// let yaml = "a:\n- 1\n- 2\nb: []\n";
+///
+/// let entries = MapEntries::<String, Vec<u32>, _>::from_str(yaml)
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(entries, vec![("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])]);
+/// ```
+pub struct MapEntries<K: DeserializeOwned, V: DeserializeOwned, R: BufRead = BufReader<FileSource>>
+{
+    chunks: Chunks<R>,
+    phantom: PhantomData<(K, V)>,
+}
+
+impl<K: DeserializeOwned, V: DeserializeOwned> MapEntries<K, V> {
+    /// Creates a new `MapEntries` iterator over the YAML file at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the YAML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn new(path: &Path) -> crate::Result<Self> {
+        Ok(Self::from_reader(FileSource::open_buffered(path)?))
+    }
+}
+
+impl<'a, K: DeserializeOwned, V: DeserializeOwned> MapEntries<K, V, &'a [u8]> {
+    /// Creates a new `MapEntries` iterator over the YAML documents in a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Self {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+impl<K: DeserializeOwned, V: DeserializeOwned, R: BufRead> MapEntries<K, V, R> {
+    /// Creates a new `MapEntries` iterator over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R) -> Self {
+        MapEntries {
+            chunks: Chunks::new(reader, Collection::Mapping),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: DeserializeOwned, V: DeserializeOwned, R: BufRead> Iterator for MapEntries<K, V, R> {
+    type Item = crate::Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let raw = match self.chunks.next_chunk()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err.into())),
+        };
+        Some(
+            serde_yml::from_str::<Entry<K, V>>(&raw.text)
+                .map(|Entry(k, v)| (k, v))
+                .map_err(|err| raw.error(err)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Collection {
+    Sequence,
+    Mapping,
+}
+
+/// Splits YAML documents into the text of the elements or entries of their top-level collection.
+struct Chunks<R> {
+    reader: R,
+    collection: Collection,
+    /// Numbers the documents as [crate::lazy::LazyDocStart] does.
+    bounds: DocBounds,
+    /// An element started by the last line read.
+    pending: Option<RawDoc>,
+    done: bool,
+}
+
+impl<R: BufRead> Chunks<R> {
+    fn new(reader: R, collection: Collection) -> Self {
+        Chunks {
+            reader,
+            collection,
+            bounds: DocBounds::default(),
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Reads the text of the next element.
+    ///
+    /// An I/O error is returned once, after which no more elements are read.
+    fn next_chunk(&mut self) -> Option<io::Result<RawDoc>> {
+        let mut chunk = self.pending.take();
+        while !self.done {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+            let offset = self.bounds.offset();
+            self.bounds.push(&line);
+            let line_number = self.bounds.line();
+            let text = trim_line_ending(&line);
+            let started = match self.classify(text) {
+                Line::Blank | Line::Continue if chunk.is_some() => {
+                    if let Some(chunk) = &mut chunk {
+                        chunk.text.push_str(&line);
+                        chunk.end_line = line_number;
+                    }
+                    continue;
+                }
+                Line::Blank => continue,
+                Line::End => None,
+                Line::Continue | Line::Start => Some(line),
+                Line::StartWith(text) => Some(text),
+            };
+            let started = started.map(|text| RawDoc {
+                index: self.bounds.index(),
+                offset,
+                start_line: line_number,
+                end_line: line_number,
+                text,
+            });
+            if chunk.is_some() {
+                self.pending = started;
+                break;
+            }
+            chunk = started;
+        }
+        chunk.map(Ok)
+    }
+
+    fn classify(&self, line: &str) -> Line {
+        if let Some(rest) = marker_rest(line, "---") {
+            // content on the marker line, other than a tag or anchor, starts an element
+            let rest = strip_comment(rest).trim();
+            let properties = rest
+                .split_whitespace()
+                .all(|t| t.starts_with('!') || t.starts_with('&'));
+            return if properties {
+                Line::End
+            } else {
+                Line::StartWith(format!("{rest}\n"))
+            };
+        }
+        if marker_rest(line, "...").is_some() || line.starts_with('%') {
+            return Line::End;
+        }
+        let content = strip_comment(line).trim();
+        if content.is_empty() {
+            return Line::Blank;
+        }
+        if line.starts_with([' ', '\t']) {
+            return Line::Continue;
+        }
+        match (self.collection, content) {
+            // an empty collection has no elements
+            (Collection::Sequence, "[]") | (Collection::Mapping, "{}") => Line::End,
+            // the elements of a sequence in a top-level mapping need not be indented
+            (Collection::Mapping, _) if marker_rest(line, "-").is_some() => Line::Continue,
+            _ => Line::Start,
+        }
+    }
+}
+
+/// What a line means for the element being read.
+enum Line {
+    /// A blank or comment line, which belongs to the current element if there is one.
+    Blank,
+    /// Content that continues the current element, or starts one if there is none.
+    Continue,
+    /// Content that starts a new element.
+    Start,
+    /// Content that starts a new element with the given text rather than the whole line.
+    StartWith(String),
+    /// A document marker or directive, which ends the current element.
+    End,
+}
+
+/// A mapping with exactly one entry.
+struct Entry<K, V>(K, V);
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for Entry<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(EntryVisitor(PhantomData))
+    }
+}
+
+struct EntryVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for EntryVisitor<K, V> {
+    type Value = Entry<K, V>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a mapping entry")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some((k, v)) = map.next_entry()? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(2, &self));
+        }
+        Ok(Entry(k, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Read;
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::append::append_or_new;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    struct Item {
+        id: u32,
+        tags: Vec<String>,
+        note: String,
+    }
+
+    fn item(id: u32) -> Item {
+        Item {
+            id,
+            tags: vec!["a".to_string(), "b".to_string()],
+            note: "line one\nline two\n".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_seq_items_from_appended_file() {
+        // GIVEN batches of items appended to a file, including an empty one
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.yml");
+        append_or_new(&path, vec![item(1), item(2)]).unwrap();
+        append_or_new(&path, Vec::<Item>::new()).unwrap();
+        append_or_new(&path, vec![item(3)]).unwrap();
+
+        // WHEN the items are streamed
+        let items = SeqItems::<Item>::new(&path)
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+
+        // THEN every item is read
+        assert_eq!(items, vec![item(1), item(2), item(3)]);
+    }
+
+    #[test]
+    fn test_map_entries_from_appended_file() {
+        // GIVEN mappings appended to a file, with sequences that are not indented
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("entries.yml");
+        let first = BTreeMap::from([("a".to_string(), item(1)), ("b".to_string(), item(2))]);
+        append_or_new(&path, &first).unwrap();
+        let second = BTreeMap::from([("c".to_string(), vec![item(3)])]);
+        append_or_new(&path, &second).unwrap();
+
+        // WHEN the entries are streamed
+        let entries = MapEntries::<String, serde_yml::Value>::new(&path)
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+
+        // THEN every entry is read
+        let keys = entries.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert_eq!(
+            serde_yml::from_value::<Vec<Item>>(entries[2].1.clone()).unwrap(),
+            vec![item(3)]
+        );
+    }
+
+    #[test]
+    fn test_bad_element_reports_lines() {
+        // GIVEN an element that does not match the type
+        let yaml = "# items\n- 1\n- - nested\n  - list\n---\n- 3\n";
+
+        // WHEN the items are streamed
+        let items = SeqItems::<u32, _>::from_str(yaml).collect::<Vec<_>>();
+
+        // THEN the element is reported with its document and lines
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap(), &1);
+        let Err(crate::Error::DocumentError(err)) = &items[1] else {
+            panic!("expected a document error");
+        };
+        assert_eq!((err.index, err.start_line, err.end_line), (0, 3, 4));
+        assert_eq!(items[2].as_ref().unwrap(), &3);
+    }
+
+    #[test]
+    fn test_markers_directives_and_tags() {
+        // GIVEN documents with directives, tags and content on the marker line
+        let yaml = "%YAML 1.2\n--- !list\n- 1\n...\n--- - 2\n- 3\n";
+
+        // WHEN the items are streamed
+        let items = SeqItems::<u32, _>::from_str(yaml)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+
+        // THEN the elements of each document are read
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_not_a_sequence() {
+        // GIVEN a mapping
+        let yaml = "a: 1\n";
+
+        // WHEN streamed as a sequence
+        let items = SeqItems::<u32, _>::from_str(yaml).collect::<Vec<_>>();
+
+        // THEN it is an error
+        assert!(matches!(items[..], [Err(crate::Error::DocumentError(_))]));
+    }
+
+    /// A reader of a sequence that never ends.
+    struct Endless {
+        next: u32,
+        buf: Vec<u8>,
+    }
+
+    impl Read for Endless {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            if self.buf.is_empty() {
+                self.buf = format!("- {}\n", self.next).into_bytes();
+                self.next += 1;
+            }
+            let n = out.len().min(self.buf.len());
+            out[..n].copy_from_slice(&self.buf[..n]);
+            self.buf.drain(..n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_document_is_not_materialized() {
+        // GIVEN a document with no end
+        let reader = BufReader::new(Endless {
+            next: 0,
+            buf: Vec::new(),
+        });
+
+        // WHEN the first items are streamed
+        let items = SeqItems::<u32, _>::from_reader(reader)
+            .take(3)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+
+        // THEN they are read without reaching the end
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+}
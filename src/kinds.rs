@@ -0,0 +1,269 @@
+//! Provides dispatch of YAML documents to types by their `apiVersion` and `kind` fields, as used
+//! by Kubernetes manifests and similar multi-document streams.
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+
+use serde::de::DeserializeOwned;
+use serde_yml::Value;
+
+use crate::compress::FileSource;
+use crate::lazy::LazyValues;
+
+type Handler<D> = Box<dyn Fn(Value) -> Result<D, serde_yml::Error>>;
+
+/// A document dispatched by a [KindRegistry].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Manifest<D> {
+    /// A document of a registered `apiVersion` and `kind`.
+    Known(D),
+    /// A document of an `apiVersion` and `kind` that is not registered, or without them.
+    Unknown(Value),
+}
+
+/// A registry of the types that documents are deserialized into according to their `apiVersion`
+/// and `kind` fields.
+pub struct KindRegistry<D> {
+    handlers: HashMap<(String, String), Handler<D>>,
+}
+
+impl<D> Default for KindRegistry<D> {
+    fn default() -> Self {
+        KindRegistry {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<D> fmt::Debug for KindRegistry<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KindRegistry")
+            .field("kinds", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<D: 'static> KindRegistry<D> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the type `T` for documents with `api_version` and `kind`, converting each into
+    /// `D` with `f`.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_version` - The value of the `apiVersion` field, such as `apps/v1`.
+    /// * `kind` - The value of the `kind` field, such as `Deployment`.
+    /// * `f` - Converts the deserialized document, such as an enum variant constructor.
+    pub fn register<T, F>(mut self, api_version: &str, kind: &str, f: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(T) -> D + 'static,
+    {
+        self.handlers.insert(
+            (api_version.to_string(), kind.to_string()),
+            Box::new(move |value| serde_yml::from_value::<T>(value).map(&f)),
+        );
+        self
+    }
+
+    /// Deserializes a document according to its `apiVersion` and `kind`.
+    fn dispatch(&self, value: Value) -> Result<Manifest<D>, serde_yml::Error> {
+        let key = match (value.get("apiVersion"), value.get("kind")) {
+            (Some(Value::String(api_version)), Some(Value::String(kind))) => {
+                (api_version.clone(), kind.clone())
+            }
+            _ => return Ok(Manifest::Unknown(value)),
+        };
+        match self.handlers.get(&key) {
+            Some(handler) => handler(value).map(Manifest::Known),
+            None => Ok(Manifest::Unknown(value)),
+        }
+    }
+}
+
+impl<R: BufRead> LazyValues<R> {
+    /// Converts this iterator into one that dispatches each document by its `apiVersion` and
+    /// `kind` with `registry`.
+    ///
+    /// See [KindDocs].
+    pub fn kinds<D: 'static>(self, registry: KindRegistry<D>) -> KindDocs<D, R> {
+        KindDocs {
+            values: self,
+            registry,
+        }
+    }
+}
+
+/// An iterator over YAML documents dispatched by their `apiVersion` and `kind` with a
+/// [KindRegistry], created with [LazyValues::kinds].
+///
+/// Every document is yielded as a [crate::Result]. A document that cannot be parsed, or that
+/// cannot be deserialized into the type registered for it, is yielded as a
+/// [crate::Error::DocumentError].
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::kinds::{KindRegistry, Manifest};
+/// use syt::lazy::LazyValues;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Deployment {
+///     metadata: Metadata,
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Metadata {
+///     name: String,
+/// }
+///
+/// let registry = KindRegistry::new().register("apps/v1", "Deployment", |d: Deployment| d);
+///
+/// let yaml = "\
+/// apiVersion: apps/v1
+/// kind: Deployment
+/// metadata:
+///   name: web
+/// ---
+/// apiVersion: v1
+/// kind: ConfigMap
+/// ";
+/// let docs = LazyValues::from_str(yaml)
+///     .kinds(registry)
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(
+///     docs[0],
+///     Manifest::Known(Deployment { metadata: Metadata { name: "web".to_string() } })
+/// );
+/// assert!(matches!(docs[1], Manifest::Unknown(_)));
+/// ```
+pub struct KindDocs<D, R: BufRead = BufReader<FileSource>> {
+    values: LazyValues<R>,
+    registry: KindRegistry<D>,
+}
+
+impl<D: 'static, R: BufRead> Iterator for KindDocs<D, R> {
+    type Item = crate::Result<Manifest<D>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (raw, value) = match self.values.try_next_raw()? {
+            Ok(parsed) => parsed,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(self.registry.dispatch(value).map_err(|err| raw.error(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Metadata {
+        name: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Deployment {
+        metadata: Metadata,
+        spec: DeploymentSpec,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct DeploymentSpec {
+        replicas: u32,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Service {
+        metadata: Metadata,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Resource {
+        Deployment(Deployment),
+        Service(Service),
+    }
+
+    fn registry() -> KindRegistry<Resource> {
+        KindRegistry::new()
+            .register("apps/v1", "Deployment", Resource::Deployment)
+            .register("v1", "Service", Resource::Service)
+    }
+
+    const BUNDLE: &str = "\
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 3
+---
+apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+apiVersion: apps/v2
+kind: Deployment
+metadata:
+  name: future
+---
+# no kind
+name: plain
+---
+apiVersion: v1
+kind: Service
+metadata: {}
+";
+
+    #[test]
+    fn test_dispatch_bundle() {
+        // GIVEN a bundle of manifests
+        // WHEN dispatched by kind
+        let docs = LazyValues::from_str(BUNDLE)
+            .kinds(registry())
+            .collect::<Vec<_>>();
+
+        // THEN registered kinds are typed
+        assert_eq!(docs.len(), 5);
+        assert_eq!(
+            docs[0].as_ref().unwrap(),
+            &Manifest::Known(Resource::Deployment(Deployment {
+                metadata: Metadata {
+                    name: "web".to_string()
+                },
+                spec: DeploymentSpec { replicas: 3 },
+            }))
+        );
+        assert_eq!(
+            docs[1].as_ref().unwrap(),
+            &Manifest::Known(Resource::Service(Service {
+                metadata: Metadata {
+                    name: "web".to_string()
+                },
+            }))
+        );
+
+        // THEN unregistered versions and documents without a kind are raw values
+        let Ok(Manifest::Unknown(value)) = &docs[2] else {
+            panic!("expected an unknown manifest");
+        };
+        assert_eq!(value["metadata"]["name"], Value::from("future"));
+        assert!(matches!(docs[3], Ok(Manifest::Unknown(_))));
+
+        // THEN a registered kind that does not match its type is a document error
+        let Err(crate::Error::DocumentError(err)) = &docs[4] else {
+            panic!("expected a document error");
+        };
+        assert_eq!((err.index, err.start_line), (4, 20));
+    }
+}
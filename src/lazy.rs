@@ -1,21 +1,32 @@
 //! Provides a lazy iterator over YAML documents in a file, separated by "---".
-use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::io::{BufRead, BufReader};
 use std::marker::PhantomData;
 use std::path::Path;
 
 use serde::de::DeserializeOwned;
 use serde_yml::Value;
 
+use crate::boundary::DocBounds;
+use crate::compress::FileSource;
+use crate::cursor::{fnv1a, Cursor, Window, FNV_OFFSET_BASIS};
+use crate::follow::FollowDocs;
+use crate::limits::{read_line_limited, Limit, Limits};
+use crate::policy::{ErrorHandler, ErrorPolicy, Summary};
+
 /// A lazy iterator over YAML documents in a file.
 ///
 /// This struct reads a file line by line, parsing YAML documents delimited by "---".
 /// It avoids loading the entire file into memory, making it suitable for large files.
-/// Each YAML document is deserialized into a user-specified type `T`.
+/// Each YAML document is deserialized into a user-specified type `T` directly from its text,
+/// so errors point at the line and column within the document. Use [LazyValues] for documents
+/// without a known type.
 ///
 /// Note that each YAML doc is read into memory before parsing, so if the docs themselves
 /// are very large then this might not be as efficient as some may need.
 ///
+/// Files compressed with gzip or zstd are decompressed on the fly when the `gzip` or `zstd`
+/// feature is enabled. See [crate::compress].
+///
 /// Example:
 ///
 /// ```rust
@@ -99,8 +110,9 @@ use serde_yml::Value;
 /// # Ok(())
 /// # }
 /// ```
-pub struct LazyDocs<T: DeserializeOwned> {
-    lazy_values: LazyValues,
+pub struct LazyDocs<T: DeserializeOwned, R: BufRead = BufReader<FileSource>> {
+    doc_start: LazyDocStart<R>,
+    errors: ErrorHandler,
     phatom: PhantomData<T>,
 }
 
@@ -116,21 +128,205 @@ pub struct LazyDocs<T: DeserializeOwned> {
 impl<T: DeserializeOwned> LazyDocs<T> {
     pub fn new(path: &Path) -> crate::Result<Self> {
         Ok(LazyDocs::<T> {
-            lazy_values: LazyValues::new(path)?,
+            doc_start: LazyDocStart::new(path)?,
+            errors: ErrorHandler::default(),
             phatom: PhantomData,
         })
     }
+
+    /// Creates an iterator that follows the YAML file at `path` as documents are appended to it,
+    /// like `tail -f`.
+    ///
+    /// See [FollowDocs].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn follow(path: &Path) -> crate::Result<FollowDocs<T>> {
+        FollowDocs::new(path)
+    }
+}
+
+impl<'a, T: DeserializeOwned> LazyDocs<T, &'a [u8]> {
+    /// Creates a new `LazyDocs` iterator over the YAML documents in a string.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use serde::Deserialize;
+    /// use syt::lazy::LazyDocs;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq, Eq)]
+    /// struct MyDoc {
+    ///     id: u32,
+    /// }
+    ///
+    /// let docs = LazyDocs::<MyDoc, _>::from_str("id: 1\n---\nid: 2\n").collect::<Vec<_>>();
+    ///
+    /// assert_eq!(docs, vec![MyDoc { id: 1 }, MyDoc { id: 2 }]);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Self {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> LazyDocs<T, R> {
+    /// Creates a new `LazyDocs` iterator over the YAML documents read from `reader`.
+    ///
+    /// This allows documents to be read lazily from sources other than a file, such as
+    /// `std::io::stdin().lock()`, in-memory buffers, pipes, or decompression streams.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The source of the YAML documents.
+    pub fn from_reader(reader: R) -> Self {
+        Self::from_doc_start(LazyDocStart::from_reader(reader))
+    }
+
+    pub(crate) fn from_doc_start(doc_start: LazyDocStart<R>) -> Self {
+        LazyDocs::<T, R> {
+            doc_start,
+            errors: ErrorHandler::default(),
+            phatom: PhantomData,
+        }
+    }
+
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    pub(crate) fn into_doc_start(self) -> LazyDocStart<R> {
+        self.doc_start
+    }
+
+    pub(crate) fn doc_start(&self) -> &LazyDocStart<R> {
+        &self.doc_start
+    }
+
+    /// Converts this iterator into one that yields a [crate::Result] for every document.
+    ///
+    /// Unlike iterating `LazyDocs` directly, documents that cannot be deserialized are not
+    /// dropped. Instead an [crate::Error::DocumentError] is yielded carrying the index and line
+    /// range of the document along with the underlying `serde_yml` error.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::io::Write;
+    /// use tempfile::NamedTempFile;
+    /// use serde::Deserialize;
+    /// use syt::lazy::LazyDocs;
+    /// use syt::Error;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq, Eq)]
+    /// struct MyDoc {
+    ///     id: u32,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut file = NamedTempFile::new()?;
+    /// writeln!(file, "id: 1")?;
+    /// writeln!(file, "---")?;
+    /// writeln!(file, "name: no id")?;
+    /// let path = file.path();
+    ///
+    /// let mut docs = LazyDocs::<MyDoc>::new(path)?.try_iter();
+    ///
+    /// assert_eq!(docs.next().unwrap()?, MyDoc { id: 1 });
+    /// let Some(Err(Error::DocumentError(err))) = docs.next() else {
+    ///     panic!("expected a document error");
+    /// };
+    /// assert_eq!(err.index, 1);
+    /// assert_eq!(err.start_line, 2);
+    /// assert_eq!(err.end_line, 3);
+    /// assert!(docs.next().is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_iter(self) -> TryLazyDocs<T, R> {
+        TryLazyDocs { docs: self }
+    }
+
+    /// Sets what iterating this `LazyDocs` does with documents that cannot be deserialized.
+    ///
+    /// See [ErrorPolicy].
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.errors.policy = policy;
+        self
+    }
+
+    /// Sets the [Limits] on the documents read, for input that is not trusted.
+    ///
+    /// Iterating `LazyDocs` directly ends at the first limit exceeded and keeps the error in the
+    /// [Summary].
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.doc_start = self.doc_start.limits(limits);
+        self
+    }
+
+    /// The summary of the documents read so far.
+    pub fn summary(&self) -> &Summary {
+        &self.errors.summary
+    }
+
+    /// Reads the next document that can be deserialized, applying the [ErrorPolicy] to those
+    /// that cannot.
+    pub(crate) fn next_parsed(&mut self) -> Option<(RawDoc, T)> {
+        while !self.errors.stopped() {
+            let raw = match self.doc_start.next_raw()? {
+                Ok(raw) => raw,
+                Err(err) => {
+                    self.errors.error(err);
+                    return None;
+                }
+            };
+            match serde_yml::from_str::<T>(&raw.text) {
+                Ok(t) => {
+                    self.errors.ok();
+                    return Some((raw, t));
+                }
+                Err(err) => self.errors.failed(&raw, err),
+            }
+        }
+        None
+    }
+
+    fn try_next(&mut self) -> Option<crate::Result<T>> {
+        self.try_next_as::<T>()
+    }
+
+    /// Reads the next document and deserializes it into `U` rather than `T`.
+    pub(crate) fn try_next_as<U: DeserializeOwned>(&mut self) -> Option<crate::Result<U>> {
+        let raw = match self.doc_start.next_raw()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(serde_yml::from_str::<U>(&raw.text).map_err(|err| raw.error(err)))
+    }
 }
 
-impl<T: DeserializeOwned> Iterator for LazyDocs<T> {
+/// Iterating `LazyDocs` directly handles documents that cannot be deserialized with its
+/// [ErrorPolicy], which skips them by default. Use [LazyDocs::try_iter] to have them reported.
+impl<T: DeserializeOwned, R: BufRead> Iterator for LazyDocs<T, R> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(v) = self.lazy_values.next() {
-            serde_yml::from_value::<T>(v).ok()
-        } else {
-            None
-        }
+        self.next_parsed().map(|(_, t)| t)
+    }
+}
+
+/// A fallible version of [LazyDocs], created with [LazyDocs::try_iter].
+///
+/// Each document is yielded as a [crate::Result]. Documents that fail to deserialize produce an
+/// [crate::Error::DocumentError], and iteration continues with the next document. An I/O error
+/// reading the file is yielded once and ends the iteration.
+pub struct TryLazyDocs<T: DeserializeOwned, R: BufRead = BufReader<FileSource>> {
+    docs: LazyDocs<T, R>,
+}
+
+impl<T: DeserializeOwned, R: BufRead> Iterator for TryLazyDocs<T, R> {
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.docs.try_next()
     }
 }
 
@@ -169,8 +365,9 @@ impl<T: DeserializeOwned> Iterator for LazyDocs<T> {
 /// # Ok(())
 /// # }
 /// ```
-pub struct LazyValues {
-    doc_start: LazyDocStart,
+pub struct LazyValues<R: BufRead = BufReader<FileSource>> {
+    doc_start: LazyDocStart<R>,
+    errors: ErrorHandler,
 }
 
 impl LazyValues {
@@ -184,30 +381,129 @@ impl LazyValues {
     ///
     /// Returns an error if the file cannot be opened.
     pub fn new(path: &Path) -> crate::Result<Self> {
-        Ok(LazyValues {
-            doc_start: LazyDocStart::new(path)?,
-        })
+        Ok(Self::from_doc_start(LazyDocStart::new(path)?))
     }
 }
 
-impl Iterator for LazyValues {
+impl<'a> LazyValues<&'a [u8]> {
+    /// Creates a new `LazyValues` iterator over the YAML documents in a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Self {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+impl<R: BufRead> LazyValues<R> {
+    /// Creates a new `LazyValues` iterator over the YAML documents read from `reader`.
+    ///
+    /// See [LazyDocs::from_reader].
+    pub fn from_reader(reader: R) -> Self {
+        Self::from_doc_start(LazyDocStart::from_reader(reader))
+    }
+
+    pub(crate) fn from_doc_start(doc_start: LazyDocStart<R>) -> Self {
+        LazyValues {
+            doc_start,
+            errors: ErrorHandler::default(),
+        }
+    }
+
+    /// Converts this iterator into one that yields a [crate::Result] for every document.
+    ///
+    /// See [LazyDocs::try_iter].
+    pub fn try_iter(self) -> TryLazyValues<R> {
+        TryLazyValues { values: self }
+    }
+
+    /// Sets what iterating this `LazyValues` does with documents that cannot be parsed.
+    ///
+    /// See [ErrorPolicy].
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.errors.policy = policy;
+        self
+    }
+
+    /// Sets the [Limits] on the documents read.
+    ///
+    /// See [LazyDocs::limits].
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.doc_start = self.doc_start.limits(limits);
+        self
+    }
+
+    /// The summary of the documents read so far.
+    pub fn summary(&self) -> &Summary {
+        &self.errors.summary
+    }
+
+    pub(crate) fn try_next_raw(&mut self) -> Option<crate::Result<(RawDoc, Value)>> {
+        let raw = match self.doc_start.next_raw()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err)),
+        };
+        match serde_yml::from_str(&raw.text) {
+            Ok(value) => Some(Ok((raw, value))),
+            Err(err) => Some(Err(raw.error(err))),
+        }
+    }
+}
+
+/// Iterating `LazyValues` directly handles documents that cannot be parsed with its
+/// [ErrorPolicy], which skips them by default. Use [LazyValues::try_iter] to have them reported.
+impl<R: BufRead> Iterator for LazyValues<R> {
     type Item = Value;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(s) = self.doc_start.next() {
-            serde_yml::from_str(&s).ok()
-        } else {
-            None
+        while !self.errors.stopped() {
+            let raw = match self.doc_start.next_raw()? {
+                Ok(raw) => raw,
+                Err(err) => {
+                    self.errors.error(err);
+                    return None;
+                }
+            };
+            match serde_yml::from_str(&raw.text) {
+                Ok(value) => {
+                    self.errors.ok();
+                    return Some(value);
+                }
+                Err(err) => self.errors.failed(&raw, err),
+            }
         }
+        None
+    }
+}
+
+/// A fallible version of [LazyValues], created with [LazyValues::try_iter].
+pub struct TryLazyValues<R: BufRead = BufReader<FileSource>> {
+    values: LazyValues<R>,
+}
+
+impl<R: BufRead> Iterator for TryLazyValues<R> {
+    type Item = crate::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.values
+            .try_next_raw()
+            .map(|parsed| parsed.map(|(_, value)| value))
     }
 }
 
 /// A lazy iterator that yields strings representing YAML documents from a file.
 ///
-/// This struct reads a file line by line, buffering lines until a "---" separator is encountered
-/// or the end of the file is reached.  It returns a `String` representing the buffered lines
-/// for each YAML document. Documents are separated by lines starting with "---".
-/// Leading and trailing whitespace around the "---" separator are included in the document strings.
+/// This struct reads a file line by line, buffering lines until the start of the next document
+/// or the end of the file is reached.  It returns a `String` with the exact text of each YAML
+/// document, including its line endings and any `---` marker line that begins it.
+///
+/// Document boundaries are found with a YAML-aware scanner rather than by splitting on every
+/// line that starts with "---":
+///
+/// * `---` is only a document marker when followed by whitespace or the end of the line.
+///   Content after the marker (e.g. `--- !tag`) stays with the document it begins.
+/// * `...` ends a document.
+/// * `%YAML` and `%TAG` directives start the document that follows them.
+/// * Lines of a block scalar (`|` or `>`) are never mistaken for directives.
+/// * Documents with no content, such as a trailing `---`, are skipped.
 ///
 /// It avoids loading the entire file into memory, making it suitable for processing large files
 /// containing multiple YAML documents.
@@ -239,8 +535,18 @@ impl Iterator for LazyValues {
 /// # Ok(())
 /// # }
 /// ```
-pub struct LazyDocStart {
-    lines: Lines<BufReader<File>>,
+pub struct LazyDocStart<R: BufRead = BufReader<FileSource>> {
+    reader: R,
+    bounds: DocBounds,
+    /// The text of the current document read so far.
+    text: String,
+    end: Option<usize>,
+    done: bool,
+    /// The checksum of the bytes before the current document.
+    checksum: u64,
+    /// The last bytes before the current document.
+    window: Window,
+    limits: Limits,
 }
 
 impl LazyDocStart {
@@ -252,35 +558,222 @@ impl LazyDocStart {
     ///
     /// # Errors
     ///
-    /// Returns an error if the file cannot be opened.
+    /// Returns an error if the file cannot be opened, or is compressed and the cargo feature for
+    /// its compression is not enabled. See [crate::compress].
     pub fn new(path: &Path) -> crate::Result<Self> {
-        let file = File::open(path)?;
-        let buf = BufReader::new(file);
-        Ok(LazyDocStart { lines: buf.lines() })
+        Ok(LazyDocStart::from_reader(FileSource::open_buffered(path)?))
     }
 }
 
-impl Iterator for LazyDocStart {
-    type Item = String;
+impl<'a> LazyDocStart<&'a [u8]> {
+    /// Creates a new `LazyDocStart` iterator over the YAML documents in a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str) -> Self {
+        Self::from_reader(s.as_bytes())
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = Vec::new();
+impl<R: BufRead> LazyDocStart<R> {
+    /// Creates a new `LazyDocStart` iterator over the YAML documents read from `reader`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::io::Cursor;
+    /// use syt::lazy::LazyDocStart;
+    ///
+    /// let reader = Cursor::new(b"title: Doc 1\n---\ntitle: Doc 2\n".to_vec());
+    ///
+    /// let doc_starts = LazyDocStart::from_reader(reader).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(doc_starts, vec!["title: Doc 1\n", "---\ntitle: Doc 2\n"]);
+    /// ```
+    pub fn from_reader(reader: R) -> Self {
+        Self::from_reader_at(reader, 0, 0, 0)
+    }
+
+    /// Creates a `LazyDocStart` for a reader positioned at the start of a document.
+    ///
+    /// `index` is the index of that document, `line` the number of lines and `offset` the number
+    /// of bytes that precede it.
+    pub(crate) fn from_reader_at(reader: R, index: usize, line: usize, offset: u64) -> Self {
+        LazyDocStart {
+            reader,
+            bounds: DocBounds::at(index, line, offset),
+            text: String::new(),
+            end: None,
+            done: false,
+            checksum: FNV_OFFSET_BASIS,
+            window: Window::default(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Sets the [Limits] on the documents read.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Continues the checksums of the bytes before the document at which the reader is
+    /// positioned.
+    pub(crate) fn with_checksums(mut self, checksum: u64, window: Window) -> Self {
+        self.checksum = checksum;
+        self.window = window;
+        self
+    }
+
+    /// The position of the start of the next document.
+    pub(crate) fn cursor(&self) -> Cursor {
+        Cursor {
+            offset: self.bounds.start_offset(),
+            index: self.bounds.index(),
+            line: self.bounds.start_line(),
+            checksum: self.checksum,
+            window_checksum: self.window.checksum(),
+        }
+    }
+
+    /// Stops reading before the document with index `end`.
+    pub(crate) fn until(mut self, end: usize) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// The number of bytes read so far.
+    pub(crate) fn offset(&self) -> u64 {
+        self.bounds.offset()
+    }
+
+    /// Reads the next document along with its location.
+    ///
+    /// An I/O error, or a [Limits] error for the bytes, lines or number of documents, is returned
+    /// once, after which no more documents are read. A document exceeding the depth or alias
+    /// limits is returned as an error and reading continues.
+    pub(crate) fn next_raw(&mut self) -> Option<crate::Result<RawDoc>> {
         loop {
-            let line = self.lines.next();
-            if let Some(Ok(line)) = line {
-                if line.starts_with("---") && !buf.is_empty() {
-                    break;
-                } else {
-                    buf.push(line);
+            if self.done || self.end.is_some_and(|e| self.bounds.index() >= e) {
+                return None;
+            }
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    self.done = true;
+                    let span = self.bounds.finish();
+                    let text = self.take_text(String::new());
+                    return span.raw(text).map(|raw| self.check(raw));
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
                 }
-            } else {
-                break;
+            };
+            if let Some(span) = self.bounds.push(&line) {
+                // the line starts the next document
+                let text = self.take_text(line);
+                if let Some(raw) = span.raw(text) {
+                    return Some(self.check(raw));
+                }
+                continue;
+            }
+            self.text.push_str(&line);
+            if let Some(max) = self
+                .limits
+                .max_doc_bytes
+                .filter(|max| self.text.len() > *max)
+            {
+                self.done = true;
+                return Some(Err(limit_error(
+                    Limit::DocumentBytes(max),
+                    self.bounds.index(),
+                    self.bounds.line(),
+                )));
             }
         }
-        if !buf.is_empty() {
-            Some(buf.join("\n"))
-        } else {
-            None
+    }
+
+    /// Replaces the text of the document that has ended with `next`, adding it to the checksums.
+    fn take_text(&mut self, next: String) -> String {
+        let text = std::mem::replace(&mut self.text, next);
+        self.checksum = fnv1a(self.checksum, text.as_bytes());
+        self.window.push(text.as_bytes());
+        text
+    }
+
+    /// Checks a document against the limits on the number of documents and their events.
+    fn check(&mut self, raw: RawDoc) -> crate::Result<RawDoc> {
+        if let Some(max) = self.limits.max_docs.filter(|max| raw.index >= *max) {
+            self.done = true;
+            return Err(limit_error(
+                Limit::Documents(max),
+                raw.index,
+                raw.start_line,
+            ));
+        }
+        if let Some(limit) = self.limits.check_events(&raw.text) {
+            return Err(limit_error(limit, raw.index, raw.start_line));
+        }
+        Ok(raw)
+    }
+
+    /// Reads the next line, checking it against the line and document byte limits.
+    fn read_line(&mut self) -> crate::Result<Option<String>> {
+        let mut buf = Vec::new();
+        let cap = self.limits.line_cap();
+        let n = read_line_limited(&mut self.reader, &mut buf, cap)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if n > cap {
+            let line = self.bounds.line() + 1;
+            return Err(limit_error(
+                self.limits.long_line(n),
+                self.bounds.index(),
+                line,
+            ));
+        }
+        let line = String::from_utf8(buf).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        Ok(Some(line))
+    }
+}
+
+impl<R: BufRead> Iterator for LazyDocStart<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_raw()?.ok().map(|raw| raw.text)
+    }
+}
+
+fn limit_error(limit: Limit, index: usize, line: usize) -> crate::Error {
+    crate::LimitError { limit, index, line }.into()
+}
+
+/// The text of a document and where it was found.
+pub(crate) struct RawDoc<S = String> {
+    pub(crate) index: usize,
+    pub(crate) offset: u64,
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+    pub(crate) text: S,
+}
+
+impl<S> RawDoc<S> {
+    pub(crate) fn error(&self, error: serde_yml::Error) -> crate::Error {
+        self.document_error(error).into()
+    }
+
+    pub(crate) fn document_error(&self, error: serde_yml::Error) -> crate::DocumentError {
+        crate::DocumentError {
+            index: self.index,
+            start_line: self.start_line,
+            end_line: self.end_line,
+            error,
         }
     }
 }
@@ -425,4 +918,147 @@ mod test {
 
         assert!(docs.next().is_none());
     }
+
+    #[test]
+    fn test_lazy_docs_skips_bad_doc_and_continues() {
+        // GIVEN a file with a bad document between two good ones
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "title: Doc 1").unwrap();
+        writeln!(file, "content: This is the first document.").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "title: Bad Doc").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "title: Doc 3").unwrap();
+        writeln!(file, "content: This is the third document.").unwrap();
+        let path = file.path();
+
+        // WHEN iterating a LazyDocs iterator
+        let docs = LazyDocs::<TestDoc>::new(path)
+            .unwrap()
+            .map(|doc| doc.title)
+            .collect::<Vec<_>>();
+
+        // THEN the bad document is skipped and iteration does not end early
+        assert_eq!(docs, vec!["Doc 1".to_string(), "Doc 3".to_string()]);
+    }
+
+    #[test]
+    fn test_lazy_docs_try_iter_reports_bad_doc() {
+        // GIVEN a file with a bad document between two good ones
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "title: Doc 1").unwrap();
+        writeln!(file, "content: This is the first document.").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "title: Bad Doc").unwrap();
+        writeln!(file, "id: 2").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "title: Doc 3").unwrap();
+        writeln!(file, "content: This is the third document.").unwrap();
+        let path = file.path();
+
+        // WHEN creating a fallible LazyDocs iterator
+        let mut docs = LazyDocs::<TestDoc>::new(path).unwrap().try_iter();
+
+        // THEN the first document is Ok
+        let doc1 = docs.next().unwrap().unwrap();
+        assert_eq!(doc1.title, "Doc 1");
+
+        // THEN the second document is an error with its index and lines
+        let Some(Err(crate::Error::DocumentError(err))) = docs.next() else {
+            panic!("expected a document error");
+        };
+        assert_eq!(err.index, 1);
+        assert_eq!(err.start_line, 4);
+        assert_eq!(err.end_line, 6);
+        assert!(err
+            .to_string()
+            .starts_with("document 1 (lines 4-6) failed: "));
+
+        // THEN the third document is Ok
+        let doc3 = docs.next().unwrap().unwrap();
+        assert_eq!(doc3.title, "Doc 3");
+        assert!(docs.next().is_none());
+    }
+
+    #[test]
+    fn test_lazy_values_try_iter_reports_bad_yaml() {
+        // GIVEN a file where the second document is not valid YAML
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "a: 1").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "a: [1, 2").unwrap();
+        writeln!(file, "---").unwrap();
+        writeln!(file, "a: 3").unwrap();
+        let path = file.path();
+
+        // WHEN creating a fallible LazyValues iterator
+        let results = LazyValues::new(path)
+            .unwrap()
+            .try_iter()
+            .collect::<Vec<_>>();
+
+        // THEN the invalid document is reported and the others parse
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            &results[1],
+            Err(crate::Error::DocumentError(err)) if err.index == 1 && err.start_line == 2
+        ));
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn test_lazy_docs_from_reader() {
+        // GIVEN an in-memory reader with two YAML documents
+        let reader = std::io::Cursor::new(
+            b"title: Doc 1\ncontent: first\n---\ntitle: Doc 2\ncontent: second\n".to_vec(),
+        );
+
+        // WHEN creating a LazyDocs iterator from the reader
+        let docs = LazyDocs::<TestDoc, _>::from_reader(reader).collect::<Vec<_>>();
+
+        // THEN it should yield both documents
+        assert_eq!(
+            docs,
+            vec![
+                TestDoc {
+                    title: "Doc 1".to_string(),
+                    content: "first".to_string()
+                },
+                TestDoc {
+                    title: "Doc 2".to_string(),
+                    content: "second".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lazy_values_from_str() {
+        // GIVEN a string with two YAML documents
+        let s = "a: 1\n---\na: 2\n";
+
+        // WHEN creating a LazyValues iterator from the string
+        let values = LazyValues::from_str(s).collect::<Vec<_>>();
+
+        // THEN it should yield both values
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0]["a"], Value::from(1));
+        assert_eq!(values[1]["a"], Value::from(2));
+    }
+
+    #[test]
+    fn test_lazy_doc_start_from_chained_reader() {
+        // GIVEN a reader made of two chained readers
+        let first: &[u8] = b"a: 1\n";
+        let second: &[u8] = b"---\na: 2\n";
+        let reader = std::io::BufReader::new(std::io::Read::chain(first, second));
+
+        // WHEN creating a LazyDocStart iterator from the reader
+        let docs = LazyDocStart::from_reader(reader).collect::<Vec<_>>();
+
+        // THEN the documents span the chained readers
+        assert_eq!(docs, vec!["a: 1\n".to_string(), "---\na: 2\n".to_string()]);
+    }
 }
@@ -3,8 +3,29 @@
 use std::{error::Error as StdError, fmt};
 
 pub mod append;
+pub mod appender;
+#[cfg(feature = "tokio")]
+pub mod async_lazy;
+mod boundary;
 pub mod comments;
+pub mod compress;
+pub mod cursor;
+pub mod follow;
+pub mod front_matter;
+pub mod header;
+pub mod index;
+pub mod items;
+pub mod kinds;
 pub mod lazy;
+pub mod limits;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multi;
+#[cfg(feature = "rayon")]
+pub mod par;
+pub mod policy;
+pub mod spanned;
+pub mod tagged;
 
 /// Error enum for errors thrown by functions in this crate.
 #[derive(Debug)]
@@ -12,14 +33,101 @@ pub enum Error {
     IoError(std::io::Error),
     YamlError(serde_yml::Error),
     FromUtf8Error(std::string::FromUtf8Error),
+    DocumentError(DocumentError),
+    MissingHeader,
+    FileError(FileError),
+    StaleCursor,
+    LimitExceeded(LimitError),
 }
 
+/// Error for a single YAML document that could not be deserialized.
+///
+/// This is produced by the fallible lazy iterators (see [lazy::LazyDocs::try_iter]) and identifies
+/// the offending document so that it can be reported instead of silently dropped.
+#[derive(Debug)]
+pub struct DocumentError {
+    /// The zero-based index of the document in the stream.
+    pub index: usize,
+    /// The one-based line on which the document starts.
+    pub start_line: usize,
+    /// The one-based line on which the document ends (inclusive).
+    pub end_line: usize,
+    /// The underlying parse error.
+    pub error: serde_yml::Error,
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "document {} (lines {}-{}) failed: {}",
+            self.index, self.start_line, self.end_line, self.error
+        )
+    }
+}
+
+impl StdError for DocumentError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Error in one of several files read together, such as by [multi::MultiFileDocs].
+#[derive(Debug)]
+pub struct FileError {
+    /// The file in which the error occurred.
+    pub path: std::path::PathBuf,
+    /// The error.
+    pub error: Box<Error>,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl StdError for FileError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// Error for input that exceeds one of the configured [limits::Limits].
+#[derive(Debug)]
+pub struct LimitError {
+    /// The limit that was exceeded.
+    pub limit: limits::Limit,
+    /// The zero-based index of the document in which it was exceeded.
+    pub index: usize,
+    /// The one-based line at which it was exceeded. For the depth and alias limits, this is the
+    /// line on which the document starts.
+    pub line: usize,
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "document {} (line {}) exceeds the {}",
+            self.index, self.line, self.limit
+        )
+    }
+}
+
+impl StdError for LimitError {}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::IoError(err) => write!(f, "IO Error: {}", err),
             Error::YamlError(err) => write!(f, "YAML Error: {}", err),
             Error::FromUtf8Error(err) => write!(f, "FromUtf8 Error: {}", err),
+            Error::DocumentError(err) => write!(f, "Document Error: {}", err),
+            Error::MissingHeader => write!(f, "Missing Header: the file has no documents"),
+            Error::FileError(err) => write!(f, "File Error: {}", err),
+            Error::StaleCursor => write!(f, "Stale Cursor: the file changed before the cursor"),
+            Error::LimitExceeded(err) => write!(f, "Limit Exceeded: {}", err),
         }
     }
 }
@@ -30,6 +138,11 @@ impl StdError for Error {
             Error::IoError(err) => Some(err),
             Error::YamlError(err) => Some(err),
             Error::FromUtf8Error(err) => Some(err),
+            Error::DocumentError(err) => Some(err),
+            Error::MissingHeader => None,
+            Error::FileError(err) => Some(err),
+            Error::StaleCursor => None,
+            Error::LimitExceeded(err) => Some(err),
         }
     }
 }
@@ -52,5 +165,23 @@ impl From<std::string::FromUtf8Error> for Error {
     }
 }
 
+impl From<DocumentError> for Error {
+    fn from(err: DocumentError) -> Self {
+        Error::DocumentError(err)
+    }
+}
+
+impl From<FileError> for Error {
+    fn from(err: FileError) -> Self {
+        Error::FileError(err)
+    }
+}
+
+impl From<LimitError> for Error {
+    fn from(err: LimitError) -> Self {
+        Error::LimitExceeded(err)
+    }
+}
+
 /// This crate's result type for [Error].
 pub type Result<T> = std::result::Result<T, Error>;
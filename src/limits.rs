@@ -0,0 +1,314 @@
+//! Provides limits on the resources used to read untrusted YAML documents.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead};
+
+// The parser is an undocumented module of serde_yml, so the dependency is pinned to an exact
+// version and it is only used here.
+use serde_yml::libyml::parser::{Anchor, Event, Parser};
+
+/// Limits on the documents read by a lazy iterator.
+///
+/// Every limit is off by default. Set them with [crate::lazy::LazyValues::limits],
+/// [crate::lazy::LazyDocs::limits] or [crate::lazy::LazyDocStart::limits].
+///
+/// Exceeding a limit yields a [crate::Error::LimitExceeded]. The byte, line and count limits are
+/// checked as the source is read, so nothing larger than them is buffered, and end iteration.
+/// The depth and alias limits are checked before a document is deserialized, and only that
+/// document fails.
+///
+/// Example:
+///
+/// ```rust
+/// use syt::lazy::LazyValues;
+/// use syt::limits::{Limit, Limits};
+/// use syt::Error;
+///
+/// let yaml = "a: 1\n---\nb: [[[[1]]]]\n---\nc: 3\n";
+/// let limits = Limits {
+///     max_depth: Some(3),
+///     ..Limits::default()
+/// };
+///
+/// let values = LazyValues::from_str(yaml).limits(limits).try_iter().collect::<Vec<_>>();
+///
+/// assert!(values[0].is_ok());
+/// let Err(Error::LimitExceeded(err)) = &values[1] else {
+///     panic!("expected a limit error");
+/// };
+/// assert_eq!(err.limit, Limit::Depth(3));
+/// assert_eq!(err.index, 1);
+/// assert!(values[2].is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of bytes in a document, including its line endings and `---` line.
+    pub max_doc_bytes: Option<usize>,
+    /// The maximum number of bytes in a line, including its line ending.
+    pub max_line_bytes: Option<usize>,
+    /// The maximum number of documents.
+    pub max_docs: Option<usize>,
+    /// The maximum nesting depth of sequences and mappings in a document.
+    pub max_depth: Option<usize>,
+    /// The maximum number of nodes in a document produced by expanding aliases. Each alias
+    /// counts as the number of nodes in the anchored node it refers to, so nested aliases, as in
+    /// a "billion laughs" document, are counted in full.
+    pub max_alias_expansions: Option<usize>,
+}
+
+/// A limit that was exceeded, with its configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    DocumentBytes(usize),
+    LineBytes(usize),
+    Documents(usize),
+    Depth(usize),
+    AliasExpansions(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::DocumentBytes(max) => write!(f, "maximum of {max} bytes per document"),
+            Limit::LineBytes(max) => write!(f, "maximum of {max} bytes per line"),
+            Limit::Documents(max) => write!(f, "maximum of {max} documents"),
+            Limit::Depth(max) => write!(f, "maximum nesting depth of {max}"),
+            Limit::AliasExpansions(max) => write!(f, "maximum of {max} alias expansions"),
+        }
+    }
+}
+
+impl Limits {
+    /// The most bytes of a line that need to be read to check the limits.
+    pub(crate) fn line_cap(&self) -> usize {
+        self.max_line_bytes
+            .unwrap_or(usize::MAX)
+            .min(self.max_doc_bytes.unwrap_or(usize::MAX))
+    }
+
+    /// The limit exceeded by a line of `len` bytes that is longer than [Limits::line_cap].
+    pub(crate) fn long_line(&self, len: usize) -> Limit {
+        match self.max_line_bytes {
+            Some(max) if len > max => Limit::LineBytes(max),
+            _ => Limit::DocumentBytes(self.max_doc_bytes.unwrap_or(usize::MAX)),
+        }
+    }
+
+    /// Checks the nesting depth and alias expansions of the text of a document.
+    ///
+    /// Text that cannot be parsed is left for the deserializer to report.
+    pub(crate) fn check_events(&self, text: &str) -> Option<Limit> {
+        if self.max_depth.is_none() && self.max_alias_expansions.is_none() {
+            return None;
+        }
+        let max_depth = self.max_depth.unwrap_or(usize::MAX);
+        let max_expansions = self.max_alias_expansions.unwrap_or(usize::MAX);
+        let mut parser = Parser::new(Cow::Borrowed(text.as_bytes()));
+        // the anchor and node count of each open sequence or mapping
+        let mut open: Vec<(Option<Anchor>, usize)> = Vec::new();
+        let mut sizes = BTreeMap::new();
+        let mut expansions = 0usize;
+        loop {
+            let (event, _) = parser.parse_next_event().ok()?;
+            let (anchor, nodes) = match event {
+                Event::StreamEnd => return None,
+                Event::SequenceStart(start) => {
+                    open.push((start.anchor, 1));
+                    if open.len() > max_depth {
+                        return Some(Limit::Depth(max_depth));
+                    }
+                    continue;
+                }
+                Event::MappingStart(start) => {
+                    open.push((start.anchor, 1));
+                    if open.len() > max_depth {
+                        return Some(Limit::Depth(max_depth));
+                    }
+                    continue;
+                }
+                Event::SequenceEnd | Event::MappingEnd => match open.pop() {
+                    Some(closed) => closed,
+                    None => continue,
+                },
+                Event::Scalar(scalar) => (scalar.anchor, 1),
+                Event::Alias(anchor) => {
+                    let nodes = sizes.get(&anchor).copied().unwrap_or(1);
+                    expansions = expansions.saturating_add(nodes);
+                    if expansions > max_expansions {
+                        return Some(Limit::AliasExpansions(max_expansions));
+                    }
+                    (None, nodes)
+                }
+                Event::StreamStart | Event::DocumentStart | Event::DocumentEnd => continue,
+            };
+            if let Some(anchor) = anchor {
+                sizes.insert(anchor, nodes);
+            }
+            if let Some((_, parent)) = open.last_mut() {
+                *parent = parent.saturating_add(nodes);
+            }
+        }
+    }
+}
+
+/// Reads a line into `buf`, stopping once it has more than `max` bytes.
+///
+/// Returns the number of bytes read, which is more than `max` if the line was cut short.
+pub(crate) fn read_line_limited<R: BufRead>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> io::Result<usize> {
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if available.is_empty() {
+            return Ok(buf.len());
+        }
+        let (line_end, found) = match available.iter().position(|b| *b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (available.len(), false),
+        };
+        let room = max.saturating_add(1) - buf.len();
+        let n = line_end.min(room);
+        buf.extend_from_slice(&available[..n]);
+        reader.consume(n);
+        if (found && n == line_end) || buf.len() > max {
+            return Ok(buf.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lazy::{LazyDocStart, LazyValues};
+    use crate::Error;
+
+    fn limit_error<T>(result: &crate::Result<T>) -> &crate::LimitError {
+        match result {
+            Err(Error::LimitExceeded(err)) => err,
+            _ => panic!("expected a limit error"),
+        }
+    }
+
+    #[test]
+    fn test_read_line_limited() {
+        // GIVEN lines
+        let mut reader = "abc\nabcdef\nab".as_bytes();
+        let mut buf = Vec::new();
+
+        // WHEN read with a limit of 4 bytes
+        // THEN lines within the limit are read whole and longer ones are cut short
+        assert_eq!(read_line_limited(&mut reader, &mut buf, 4).unwrap(), 4);
+        assert_eq!(buf, b"abc\n");
+        buf.clear();
+        assert_eq!(read_line_limited(&mut reader, &mut buf, 4).unwrap(), 5);
+        assert_eq!(buf, b"abcde");
+    }
+
+    #[test]
+    fn test_max_line_bytes() {
+        // GIVEN a very long line
+        let yaml = format!("a: 1\n---\nb: {}\n", "x".repeat(10_000));
+        let limits = Limits {
+            max_line_bytes: Some(100),
+            ..Limits::default()
+        };
+
+        // WHEN read with a line limit
+        let docs = LazyValues::from_str(&yaml)
+            .limits(limits)
+            .try_iter()
+            .collect::<Vec<_>>();
+
+        // THEN iteration ends at the line
+        assert_eq!(docs.len(), 2);
+        let err = limit_error(&docs[1]);
+        assert_eq!(
+            (err.limit, err.index, err.line),
+            (Limit::LineBytes(100), 1, 3)
+        );
+    }
+
+    #[test]
+    fn test_max_doc_bytes() {
+        // GIVEN a document with many short lines
+        let big = (0..1000).map(|i| format!("- {i}\n")).collect::<String>();
+        let yaml = format!("a: 1\n---\n{big}---\nc: 3\n");
+        let limits = Limits {
+            max_doc_bytes: Some(64),
+            ..Limits::default()
+        };
+
+        // WHEN read with a document limit
+        let mut values = LazyValues::from_str(&yaml).limits(limits);
+        let count = values.by_ref().count();
+
+        // THEN iteration ends at the document, which is reported in the summary
+        assert_eq!(count, 1);
+        let Some(err) = &values.summary().limit_error else {
+            panic!("expected a limit error");
+        };
+        assert_eq!(err.limit, Limit::DocumentBytes(64));
+        assert_eq!(err.index, 1);
+    }
+
+    #[test]
+    fn test_max_docs() {
+        // GIVEN three documents
+        let yaml = "a: 1\n---\nb: 2\n---\nc: 3\n";
+        let limits = Limits {
+            max_docs: Some(2),
+            ..Limits::default()
+        };
+
+        // WHEN read with a document count limit
+        let mut doc_start = LazyDocStart::from_str(yaml).limits(limits);
+        let docs = std::iter::from_fn(|| doc_start.next_raw()).collect::<Vec<_>>();
+
+        // THEN the third document is an error
+        assert_eq!(docs.len(), 3);
+        assert_eq!(limit_error(&docs[2]).limit, Limit::Documents(2));
+    }
+
+    #[test]
+    fn test_max_alias_expansions() {
+        // GIVEN a "billion laughs" document
+        let yaml = "\
+a: &a [x, x, x, x, x, x, x, x, x, x]
+b: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a, *a]
+c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b, *b]
+d: &d [*c, *c, *c, *c, *c, *c, *c, *c, *c, *c]
+e: [*d, *d, *d, *d, *d, *d, *d, *d, *d, *d]
+---
+ok: *x
+";
+        let limits = Limits {
+            max_alias_expansions: Some(1000),
+            ..Limits::default()
+        };
+
+        // WHEN checked
+        let docs = LazyValues::from_str(yaml)
+            .limits(limits)
+            .try_iter()
+            .collect::<Vec<_>>();
+
+        // THEN the expansions are counted in full
+        assert_eq!(limit_error(&docs[0]).limit, Limit::AliasExpansions(1000));
+        assert!(matches!(docs[1], Err(Error::DocumentError(_))));
+        assert_eq!(
+            Limits {
+                max_alias_expansions: Some(1000),
+                ..Limits::default()
+            }
+            .check_events("a: &a [x, x]\nb: [*a, *a]\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Provides memory-mapped reading of YAML documents.
+//!
+//! This is available with the `mmap` feature.
+//!
+//! Unlike [crate::lazy::LazyDocStart], which reads a line at a time into newly allocated strings,
+//! a [MappedFile] scans the mapped bytes of the file for document boundaries and hands out each
+//! document as a `&str` slice of the mapping without copying it. This suits read-heavy processing
+//! of large local files. [crate::lazy::LazyDocs] remains the choice for other sources, such as
+//! compressed files, pipes, or files that are written to while they are read.
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use memmap2::Mmap;
+use serde::de::DeserializeOwned;
+
+use crate::boundary::DocBounds;
+use crate::compress::Compression;
+use crate::lazy::RawDoc;
+
+/// A YAML file mapped into memory.
+///
+/// Documents are split with the same rules as [crate::lazy::LazyDocStart], and are numbered and
+/// located in the same way.
+///
+/// The file must not be truncated or modified while it is mapped, which is why
+/// [MappedFile::open] is unsafe.
+///
+/// Example:
+///
+/// ```rust
+/// use std::io::Write;
+/// use serde::Deserialize;
+/// use syt::mmap::MappedFile;
+/// use tempfile::NamedTempFile;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct MyDoc {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let mut file = NamedTempFile::new()?;
+/// writeln!(file, "id: 1")?;
+/// writeln!(file, "---")?;
+/// writeln!(file, "id: 2")?;
+///
+/// // SAFETY: the temporary file is not modified while it is mapped.
+/// let mapped = unsafe { MappedFile::open(file.path())? };
+///
+/// let doc_starts = mapped.doc_starts().collect::<Vec<&str>>();
+/// assert_eq!(doc_starts, vec!["id: 1\n", "---\nid: 2\n"]);
+///
+/// let docs = mapped.docs::<MyDoc>().collect::<Vec<_>>();
+/// assert_eq!(docs, vec![MyDoc { id: 1 }, MyDoc { id: 2 }]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MappedFile {
+    map: Option<Mmap>,
+}
+
+impl MappedFile {
+    /// Maps the YAML file at `path` into memory.
+    ///
+    /// The file is checked to be valid UTF-8 once, here, and its text is then handed out as
+    /// `&str` without checking it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the YAML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or mapped, is compressed, or is not valid
+    /// UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified, by this or any other process, while the
+    /// `MappedFile` exists. Truncating it can crash the process with `SIGBUS` when the missing
+    /// part is read, and modifying it can change the text borrowed from the mapping, so that it
+    /// is no longer valid UTF-8. Both are undefined behaviour.
+    pub unsafe fn open(path: &Path) -> crate::Result<Self> {
+        let mut file = File::open(path)?;
+        if Compression::detect_file(&mut file, path)? != Compression::None {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "compressed files cannot be memory mapped",
+            )
+            .into());
+        }
+        // mapping an empty file fails on some platforms
+        if file.metadata()?.len() == 0 {
+            return Ok(MappedFile { map: None });
+        }
+        // SAFETY: the caller guarantees that the file is not modified while it is mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        std::str::from_utf8(&map).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(MappedFile { map: Some(map) })
+    }
+
+    /// The text of the whole file.
+    pub fn as_str(&self) -> &str {
+        match &self.map {
+            // SAFETY: the mapping was checked to be UTF-8 when it was opened, and the caller of
+            // open guarantees that it has not been modified since.
+            Some(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            None => "",
+        }
+    }
+
+    /// Returns an iterator over the raw text of each document.
+    pub fn doc_starts(&self) -> MappedDocStart<'_> {
+        MappedDocStart::new(self.as_str())
+    }
+
+    /// Returns an iterator that deserializes each document into `T`.
+    pub fn docs<T: DeserializeOwned>(&self) -> MappedDocs<'_, T> {
+        MappedDocs {
+            doc_start: self.doc_starts(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the raw text of the YAML documents in a [MappedFile].
+///
+/// Each document is a slice of the mapping, including its line endings and the `---` line that
+/// starts it, as with [crate::lazy::LazyDocStart].
+pub struct MappedDocStart<'a> {
+    text: &'a str,
+    bounds: DocBounds,
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> MappedDocStart<'a> {
+    fn new(text: &'a str) -> Self {
+        MappedDocStart {
+            text,
+            bounds: DocBounds::default(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    pub(crate) fn next_raw(&mut self) -> Option<RawDoc<&'a str>> {
+        let text = self.text;
+        while self.pos < text.len() {
+            let start = self.pos;
+            self.pos = match text[start..].find('\n') {
+                Some(n) => start + n + 1,
+                None => text.len(),
+            };
+            let doc_start = self.bounds.start_offset() as usize;
+            let span = self.bounds.push(&text[start..self.pos]);
+            // a line that starts a document ends the previous one
+            if let Some(raw) = span.and_then(|span| span.raw(&text[doc_start..start])) {
+                return Some(raw);
+            }
+        }
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        let doc_start = self.bounds.start_offset() as usize;
+        self.bounds.finish().raw(&text[doc_start..])
+    }
+}
+
+impl<'a> Iterator for MappedDocStart<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_raw().map(|raw| raw.text)
+    }
+}
+
+/// An iterator that deserializes the YAML documents in a [MappedFile] into `T`.
+///
+/// Like [crate::lazy::LazyDocs], documents that cannot be deserialized are skipped. Use
+/// [MappedDocs::try_iter] to have them reported.
+pub struct MappedDocs<'a, T: DeserializeOwned> {
+    doc_start: MappedDocStart<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T: DeserializeOwned> MappedDocs<'a, T> {
+    /// Converts this iterator into one that yields a [crate::Result] for every document.
+    ///
+    /// See [crate::lazy::LazyDocs::try_iter].
+    pub fn try_iter(self) -> TryMappedDocs<'a, T> {
+        TryMappedDocs { docs: self }
+    }
+
+    fn try_next(&mut self) -> Option<crate::Result<T>> {
+        let raw = self.doc_start.next_raw()?;
+        Some(serde_yml::from_str::<T>(raw.text).map_err(|err| raw.error(err)))
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for MappedDocs<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Ok(t) = self.try_next()? {
+                return Some(t);
+            }
+        }
+    }
+}
+
+/// An iterator that yields a [crate::Result] for every YAML document in a [MappedFile].
+///
+/// This is created with [MappedDocs::try_iter].
+pub struct TryMappedDocs<'a, T: DeserializeOwned> {
+    docs: MappedDocs<'a, T>,
+}
+
+impl<T: DeserializeOwned> Iterator for TryMappedDocs<'_, T> {
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.docs.try_next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use serde::Deserialize;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::lazy::LazyDocStart;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct TestDoc {
+        id: u32,
+    }
+
+    fn mapped(text: &str) -> (NamedTempFile, MappedFile) {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        // SAFETY: the temporary file is not modified while it is mapped.
+        let mapped = unsafe { MappedFile::open(file.path()).unwrap() };
+        (file, mapped)
+    }
+
+    #[test]
+    fn test_same_as_lazy_doc_start() {
+        // GIVEN documents with markers, comments, block scalars and no trailing newline
+        let text = "# lead\nid: 1\n---\n\n--- |\n  ---\n  text\n...\n%YAML 1.2\n---\nid: 3\r\n---";
+
+        // WHEN split from a mapping
+        let (_file, mapped) = mapped(text);
+        let mut doc_start = mapped.doc_starts();
+        let mut lazy = LazyDocStart::from_str(text);
+
+        // THEN the documents and their locations are the same as LazyDocStart
+        loop {
+            match (doc_start.next_raw(), lazy.next_raw()) {
+                (Some(actual), Some(expected)) => {
+                    let expected = expected.unwrap();
+                    assert_eq!(actual.text, expected.text);
+                    assert_eq!(actual.index, expected.index);
+                    assert_eq!(actual.offset, expected.offset);
+                    assert_eq!(actual.start_line, expected.start_line);
+                    assert_eq!(actual.end_line, expected.end_line);
+                }
+                (None, None) => break,
+                _ => panic!("different number of documents"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_iter() {
+        // GIVEN a bad document between good ones
+        let (_file, mapped) = mapped("id: 1\n---\nname: no id\n---\nid: 3\n");
+
+        // WHEN deserialized
+        let docs = mapped.docs::<TestDoc>().try_iter().collect::<Vec<_>>();
+
+        // THEN the bad document is reported with its location
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[0].as_ref().unwrap(), &TestDoc { id: 1 });
+        let Err(crate::Error::DocumentError(err)) = &docs[1] else {
+            panic!("expected a document error");
+        };
+        assert_eq!((err.index, err.start_line, err.end_line), (1, 2, 3));
+        assert_eq!(docs[2].as_ref().unwrap(), &TestDoc { id: 3 });
+
+        // THEN plain iteration skips it
+        let ids = mapped.docs::<TestDoc>().map(|d| d.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        // GIVEN an empty file
+        let (_file, mapped) = mapped("");
+
+        // WHEN split
+        // THEN there are no documents
+        assert_eq!(mapped.doc_starts().count(), 0);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        // GIVEN a file that is not UTF-8
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"id: \xff\n").unwrap();
+
+        // WHEN mapped
+        // SAFETY: the temporary file is not modified while it is mapped.
+        let result = unsafe { MappedFile::open(file.path()) };
+
+        // THEN it is an error
+        assert!(matches!(result, Err(crate::Error::IoError(_))));
+    }
+}
@@ -0,0 +1,401 @@
+//! Provides lazy iteration of YAML documents across many files.
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+
+use crate::lazy::LazyDocStart;
+use crate::FileError;
+
+/// The order in which [MultiFileDocs] reads its files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOrder {
+    /// The order in which the files were listed.
+    Listed,
+    /// Lexical order of the paths.
+    Lexical,
+    /// Oldest modification time first, then lexical order of the paths.
+    Mtime,
+    /// Rotated segments oldest first, so `events.yml.2` is read before `events.yml.1`, which is
+    /// read before `events.yml`. Files with different names are in lexical order.
+    Rotation,
+}
+
+/// A document read by [MultiFileDocs] and the file it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDoc<T> {
+    /// The file the document came from.
+    pub path: PathBuf,
+    /// The zero-based index of the document in its file.
+    pub index: usize,
+    /// The deserialized document.
+    pub value: T,
+}
+
+/// A lazy iterator over the YAML documents in many files.
+///
+/// The files are given as a list, a glob pattern (with the `glob` feature), or a directory tree,
+/// and are read one at a time in a deterministic [FileOrder]. Each file is split into documents with a
+/// [LazyDocStart], so compressed files are decompressed as with [crate::lazy::LazyDocs].
+///
+/// Every document is yielded as a [crate::Result]. Errors are wrapped in a
+/// [crate::Error::FileError] naming the file. A file that cannot be opened is reported and
+/// skipped, and a document that cannot be deserialized is reported as a
+/// [crate::Error::DocumentError] within it.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::multi::{FileOrder, MultiFileDocs};
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Event {
+///     id: u32,
+/// }
+///
+/// # fn main() -> Result<(), syt::Error> {
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("events.yml"), "id: 3\n")?;
+/// std::fs::write(dir.path().join("events.yml.1"), "id: 2\n")?;
+/// std::fs::write(dir.path().join("events.yml.2"), "id: 1\n")?;
+///
+/// let docs = MultiFileDocs::<Event>::from_dir(dir.path())?
+///     .order(FileOrder::Rotation)
+///     .collect::<Result<Vec<_>, _>>()?;
+///
+/// let ids = docs.iter().map(|doc| doc.value.id).collect::<Vec<_>>();
+/// assert_eq!(ids, vec![1, 2, 3]);
+/// assert_eq!(docs[0].path, dir.path().join("events.yml.2"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiFileDocs<T: DeserializeOwned> {
+    paths: Vec<PathBuf>,
+    order: FileOrder,
+    files: Option<VecDeque<PathBuf>>,
+    current: Option<(PathBuf, LazyDocStart)>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> MultiFileDocs<T> {
+    /// Creates a `MultiFileDocs` over a list of files, read in the order listed by default.
+    pub fn from_paths<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        Self::with_order(
+            paths.into_iter().map(Into::into).collect(),
+            FileOrder::Listed,
+        )
+    }
+
+    /// Creates a `MultiFileDocs` over the files matching a glob pattern, such as
+    /// `logs/**/*.yml`, read in lexical order by default.
+    ///
+    /// This is available with the `glob` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern is invalid or a matching directory cannot be read.
+    #[cfg(feature = "glob")]
+    pub fn from_glob(pattern: &str) -> crate::Result<Self> {
+        let paths = glob::glob(pattern)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?
+            .map(|path| path.map_err(std::io::Error::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        let files = paths.into_iter().filter(|path| path.is_file()).collect();
+        Ok(Self::with_order(files, FileOrder::Lexical))
+    }
+
+    /// Creates a `MultiFileDocs` over the YAML files in a directory tree, read in lexical order
+    /// by default.
+    ///
+    /// YAML files are those named `*.yml` or `*.yaml`, optionally followed by a rotation number
+    /// and a compression extension, such as `events.yml.1` or `events.yml.gz`. Symbolic links to
+    /// directories are not followed, so links that loop back up the tree are harmless.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory in the tree cannot be read.
+    pub fn from_dir(dir: &Path) -> crate::Result<Self> {
+        let mut files = Vec::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    dirs.push(path);
+                } else if is_yaml_file(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        Ok(Self::with_order(files, FileOrder::Lexical))
+    }
+
+    fn with_order(paths: Vec<PathBuf>, order: FileOrder) -> Self {
+        MultiFileDocs {
+            paths,
+            order,
+            files: None,
+            current: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the order in which the files are read.
+    pub fn order(mut self, order: FileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The files in the order in which they are read.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.paths.clone();
+        match self.order {
+            FileOrder::Listed => {}
+            FileOrder::Lexical => paths.sort(),
+            FileOrder::Mtime => {
+                paths.sort_by_cached_key(|path| {
+                    let mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
+                    (mtime.unwrap_or(SystemTime::UNIX_EPOCH), path.clone())
+                });
+            }
+            FileOrder::Rotation => {
+                paths.sort_by_cached_key(|path| {
+                    let (base, number) = rotation(path);
+                    (base, Reverse(number))
+                });
+            }
+        }
+        paths
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for MultiFileDocs<T> {
+    type Item = crate::Result<FileDoc<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.files.is_none() {
+            self.files = Some(self.paths().into());
+        }
+        loop {
+            let Some((path, doc_start)) = &mut self.current else {
+                let path = self.files.as_mut()?.pop_front()?;
+                match LazyDocStart::new(&path) {
+                    Ok(doc_start) => self.current = Some((path, doc_start)),
+                    Err(err) => return Some(Err(file_error(path, err))),
+                }
+                continue;
+            };
+            let raw = match doc_start.next_raw() {
+                Some(Ok(raw)) => raw,
+                Some(Err(err)) => return Some(Err(file_error(path.clone(), err))),
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            return Some(match serde_yml::from_str::<T>(&raw.text) {
+                Ok(value) => Ok(FileDoc {
+                    path: path.clone(),
+                    index: raw.index,
+                    value,
+                }),
+                Err(err) => Err(file_error(path.clone(), raw.error(err))),
+            });
+        }
+    }
+}
+
+fn file_error(path: PathBuf, error: crate::Error) -> crate::Error {
+    FileError {
+        path,
+        error: Box::new(error),
+    }
+    .into()
+}
+
+/// Removes a compression extension from a file name.
+fn strip_compression(name: &str) -> &str {
+    name.strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(name)
+}
+
+/// Splits a path into the path of the file it was rotated from and its rotation number, which
+/// is 0 if it has none.
+fn rotation(path: &Path) -> (PathBuf, u64) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let stripped = strip_compression(&name);
+    if let Some((base, number)) = stripped.rsplit_once('.') {
+        if let Ok(number) = number.parse::<u64>() {
+            return (path.with_file_name(base), number);
+        }
+    }
+    (path.with_file_name(stripped), 0)
+}
+
+fn is_yaml_file(path: &Path) -> bool {
+    let (base, _) = rotation(path);
+    matches!(
+        base.extension().and_then(|ext| ext.to_str()),
+        Some("yml" | "yaml")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Event {
+        id: u32,
+    }
+
+    fn write(path: &Path, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_rotation() {
+        // GIVEN rotated, compressed and plain names
+        // WHEN split into base and rotation number
+        // THEN the number is found before any compression extension
+        assert_eq!(
+            rotation(Path::new("a/events.yml.12.gz")),
+            (PathBuf::from("a/events.yml"), 12)
+        );
+        assert_eq!(
+            rotation(Path::new("events.yml")),
+            (PathBuf::from("events.yml"), 0)
+        );
+        assert!(is_yaml_file(Path::new("events.yaml.3.zst")));
+        assert!(!is_yaml_file(Path::new("events.yml.idx")));
+        assert!(!is_yaml_file(Path::new("notes.txt")));
+    }
+
+    #[test]
+    fn test_dir_lexical() {
+        // GIVEN a directory tree with YAML and other files
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("b/2.yml"), "id: 3\n");
+        write(&dir.path().join("a.yaml"), "id: 1\n---\nid: 2\n");
+        write(&dir.path().join("b/1.yml"), "bad: doc\n");
+        write(&dir.path().join("b/notes.txt"), "id: 9\n");
+
+        // WHEN read in lexical order
+        let docs = MultiFileDocs::<Event>::from_dir(dir.path())
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        // THEN documents come from each YAML file in order with their source
+        assert_eq!(docs.len(), 4);
+        let doc = docs[1].as_ref().unwrap();
+        assert_eq!(
+            (&doc.path, doc.index, doc.value.id),
+            (&dir.path().join("a.yaml"), 1, 2)
+        );
+        let Err(crate::Error::FileError(err)) = &docs[2] else {
+            panic!("expected a file error");
+        };
+        assert_eq!(err.path, dir.path().join("b/1.yml"));
+        assert!(matches!(*err.error, crate::Error::DocumentError(_)));
+        assert_eq!(docs[3].as_ref().unwrap().value.id, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_symlink_loop() {
+        // GIVEN a directory with a symbolic link to its parent
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("a/1.yml"), "id: 1\n");
+        std::os::unix::fs::symlink("..", dir.path().join("a/loop")).unwrap();
+
+        // WHEN the tree is listed
+        let docs = MultiFileDocs::<Event>::from_dir(dir.path()).unwrap();
+
+        // THEN the link is not followed
+        assert_eq!(docs.paths(), vec![dir.path().join("a/1.yml")]);
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob_rotation() {
+        // GIVEN rotated segments of two logs
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("b.yml"), "id: 5\n");
+        write(&dir.path().join("b.yml.1"), "id: 4\n");
+        write(&dir.path().join("a.yml.10"), "id: 1\n");
+        write(&dir.path().join("a.yml.2"), "id: 2\n");
+        write(&dir.path().join("a.yml"), "id: 3\n");
+
+        // WHEN read in rotation order
+        let pattern = format!("{}/*.yml*", dir.path().display());
+        let ids = MultiFileDocs::<Event>::from_glob(&pattern)
+            .unwrap()
+            .order(FileOrder::Rotation)
+            .map(|doc| doc.unwrap().value.id)
+            .collect::<Vec<_>>();
+
+        // THEN older segments are read first
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_paths_mtime_and_missing() {
+        // GIVEN listed files with different modification times and a missing file
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("z.yml");
+        let new = dir.path().join("a.yml");
+        write(&old, "id: 1\n");
+        write(&new, "id: 2\n");
+        let now = SystemTime::now();
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(60))
+            .unwrap();
+        File::options()
+            .write(true)
+            .open(&new)
+            .unwrap()
+            .set_modified(now)
+            .unwrap();
+        let missing = dir.path().join("missing.yml");
+
+        // WHEN read as listed
+        let docs = MultiFileDocs::<Event>::from_paths([&missing, &new, &old]).collect::<Vec<_>>();
+
+        // THEN the missing file is reported and the others are read as listed
+        assert!(matches!(&docs[0], Err(crate::Error::FileError(err)) if err.path == missing));
+        assert_eq!(docs[1].as_ref().unwrap().value.id, 2);
+        assert_eq!(docs[2].as_ref().unwrap().value.id, 1);
+
+        // WHEN read in modification order
+        let ids = MultiFileDocs::<Event>::from_paths([&new, &old])
+            .order(FileOrder::Mtime)
+            .map(|doc| doc.unwrap().value.id)
+            .collect::<Vec<_>>();
+
+        // THEN the oldest file is read first
+        assert_eq!(ids, vec![1, 2]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Provides parallel deserialization of YAML documents.
+//!
+//! This is available with the `rayon` feature.
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use rayon::ThreadPoolBuilder;
+use serde::de::DeserializeOwned;
+
+use crate::compress::FileSource;
+use crate::lazy::{LazyDocStart, LazyDocs};
+
+impl<T, R> LazyDocs<T, R>
+where
+    T: DeserializeOwned + Send + 'static,
+    R: BufRead + Send + 'static,
+{
+    /// Converts this iterator into one that deserializes documents in parallel.
+    ///
+    /// See [ParLazyDocs].
+    pub fn par_iter(self) -> ParLazyDocs<T, R> {
+        ParLazyDocs {
+            doc_start: Some(self.into_doc_start()),
+            ordered: true,
+            capacity: ParLazyDocs::<T, R>::DEFAULT_CAPACITY,
+            num_threads: 0,
+            rx: None,
+            next: 0,
+            reorder: BTreeMap::new(),
+            window: Arc::new(Window {
+                next: Mutex::new(Some(0)),
+                advanced: Condvar::new(),
+            }),
+        }
+    }
+}
+
+/// An iterator that deserializes YAML documents in parallel, created with [LazyDocs::par_iter].
+///
+/// The raw text of each document is split from the source by a [LazyDocStart] on a dedicated
+/// thread, and the documents are deserialized across a rayon thread pool, with a thread per CPU
+/// by default. Parsing with `serde_yml` is usually the bottleneck when reading large files, so
+/// throughput scales with the number of threads.
+///
+/// The pool belongs to the iterator rather than being the rayon global pool, as its threads
+/// block while waiting for documents to be consumed. Other uses of rayon are not held up by a
+/// slow consumer.
+///
+/// Like [crate::lazy::TryLazyDocs], every document is yielded as a [crate::Result]. By default
+/// documents are yielded in their original order. Use [ParLazyDocs::ordered] to yield them as
+/// soon as they are deserialized instead.
+///
+/// Nothing is read until the first call to `next`.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::lazy::LazyDocs;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct MyDoc {
+///     id: u32,
+/// }
+///
+/// let yaml = (0..100).map(|id| format!("id: {id}\n")).collect::<Vec<_>>().join("---\n");
+///
+/// let docs = LazyDocs::<MyDoc, _>::from_reader(std::io::Cursor::new(yaml))
+///     .par_iter()
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(docs, (0..100).map(|id| MyDoc { id }).collect::<Vec<_>>());
+/// ```
+pub struct ParLazyDocs<T, R = BufReader<FileSource>>
+where
+    T: DeserializeOwned + Send + 'static,
+    R: BufRead + Send + 'static,
+{
+    doc_start: Option<LazyDocStart<R>>,
+    ordered: bool,
+    capacity: usize,
+    num_threads: usize,
+    rx: Option<Receiver<(usize, crate::Result<T>)>>,
+    /// The sequence number of the next document to yield when ordered.
+    next: usize,
+    /// Documents deserialized ahead of the next document to yield when ordered.
+    reorder: BTreeMap<usize, crate::Result<T>>,
+    window: Arc<Window>,
+}
+
+/// The sequence number of the next document to yield when ordered, shared with the worker
+/// threads so that they stay within the capacity of it. It is `None` once the iterator has been
+/// dropped.
+struct Window {
+    next: Mutex<Option<usize>>,
+    advanced: Condvar,
+}
+
+impl Window {
+    /// Waits until `seq` is less than `capacity` ahead of the next document to yield.
+    ///
+    /// Returns `false` if the iterator has been dropped.
+    fn wait(&self, seq: usize, capacity: usize) -> bool {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match *next {
+                None => return false,
+                Some(n) if seq < n.saturating_add(capacity) => return true,
+                Some(_) => {
+                    next = self
+                        .advanced
+                        .wait(next)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    fn set(&self, next: Option<usize>) {
+        *self.next.lock().unwrap_or_else(PoisonError::into_inner) = next;
+        self.advanced.notify_all();
+    }
+}
+
+impl<T, R> ParLazyDocs<T, R>
+where
+    T: DeserializeOwned + Send + 'static,
+    R: BufRead + Send + 'static,
+{
+    /// The default number of deserialized documents waiting to be yielded.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// Sets whether documents are yielded in their original order.
+    ///
+    /// When ordered, a slow document holds back those after it until it is deserialized.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Sets how many deserialized documents can be waiting to be yielded before the worker
+    /// threads wait for them to be consumed.
+    ///
+    /// When ordered, the worker threads also wait rather than deserialize a document `capacity`
+    /// or more places after the next one to yield, so a slow document does not let the documents
+    /// held back behind it grow without bound.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the number of threads that deserialize documents. By default, or if `num_threads` is
+    /// 0, there is one per CPU.
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    fn start(&mut self, mut doc_start: LazyDocStart<R>) -> Receiver<(usize, crate::Result<T>)> {
+        let (tx, rx) = mpsc::sync_channel(self.capacity);
+        let capacity = self.capacity;
+        let num_threads = self.num_threads;
+        let window = self.ordered.then(|| Arc::clone(&self.window));
+        thread::spawn(move || {
+            let pool = match ThreadPoolBuilder::new().num_threads(num_threads).build() {
+                Ok(pool) => pool,
+                Err(err) => {
+                    let _ = tx.send((0, Err(io::Error::other(err).into())));
+                    return;
+                }
+            };
+            let raw_docs = std::iter::from_fn(move || doc_start.next_raw()).enumerate();
+            // stops early if the iterator has been dropped
+            let _ = pool.install(|| {
+                raw_docs
+                    .par_bridge()
+                    .try_for_each_with(tx, |tx, (seq, raw)| {
+                        if let Some(window) = &window {
+                            if !window.wait(seq, capacity) {
+                                return Err(());
+                            }
+                        }
+                        let doc = match raw {
+                            Ok(raw) => {
+                                serde_yml::from_str::<T>(&raw.text).map_err(|err| raw.error(err))
+                            }
+                            Err(err) => Err(err),
+                        };
+                        tx.send((seq, doc)).map_err(|_| ())
+                    })
+            });
+        });
+        rx
+    }
+}
+
+impl<T, R> Iterator for ParLazyDocs<T, R>
+where
+    T: DeserializeOwned + Send + 'static,
+    R: BufRead + Send + 'static,
+{
+    type Item = crate::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(doc_start) = self.doc_start.take() {
+            self.rx = Some(self.start(doc_start));
+        }
+        let rx = self.rx.as_ref()?;
+        if !self.ordered {
+            return rx.recv().ok().map(|(_, doc)| doc);
+        }
+        loop {
+            if let Some(doc) = self.reorder.remove(&self.next) {
+                self.next += 1;
+                self.window.set(Some(self.next));
+                return Some(doc);
+            }
+            let (seq, doc) = rx.recv().ok()?;
+            self.reorder.insert(seq, doc);
+        }
+    }
+}
+
+impl<T, R> Drop for ParLazyDocs<T, R>
+where
+    T: DeserializeOwned + Send + 'static,
+    R: BufRead + Send + 'static,
+{
+    fn drop(&mut self) {
+        self.window.set(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct TestDoc {
+        id: u32,
+    }
+
+    fn yaml(n: u32, bad: u32) -> String {
+        (0..n)
+            .map(|id| {
+                if id == bad {
+                    "bad: doc\n".to_string()
+                } else {
+                    format!("id: {id}\n")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("---\n")
+    }
+
+    #[test]
+    fn test_par_iter_ordered() {
+        // GIVEN many documents with a bad one
+        let yaml = yaml(2000, 1234);
+
+        // WHEN deserialized in parallel
+        let docs = LazyDocs::<TestDoc, _>::from_reader(Cursor::new(yaml.clone()))
+            .par_iter()
+            .capacity(16)
+            .collect::<Vec<_>>();
+
+        // THEN the results are the same as a sequential try_iter
+        let expected = LazyDocs::<TestDoc, _>::from_reader(Cursor::new(yaml)).try_iter();
+        assert_eq!(docs.len(), 2000);
+        for (actual, expected) in docs.into_iter().zip(expected) {
+            match (actual, expected) {
+                (Ok(actual), Ok(expected)) => assert_eq!(actual, expected),
+                (Err(crate::Error::DocumentError(a)), Err(crate::Error::DocumentError(e))) => {
+                    assert_eq!((a.index, a.start_line), (e.index, e.start_line));
+                }
+                _ => panic!("results differ"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_par_iter_unordered() {
+        // GIVEN many documents with a bad one
+        let yaml = yaml(2000, 7);
+
+        // WHEN deserialized in parallel without preserving order
+        let docs = LazyDocs::<TestDoc, _>::from_reader(Cursor::new(yaml))
+            .par_iter()
+            .ordered(false)
+            .collect::<Vec<_>>();
+
+        // THEN every document is yielded
+        assert_eq!(docs.len(), 2000);
+        let mut ids = docs
+            .into_iter()
+            .filter_map(|doc| doc.ok())
+            .map(|doc| doc.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, (0..2000).filter(|id| *id != 7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_par_iter_dropped_early() {
+        // GIVEN many documents
+        let yaml = yaml(10_000, 10_000);
+
+        // WHEN only a few are taken before the iterator is dropped
+        let docs = LazyDocs::<TestDoc, _>::from_reader(Cursor::new(yaml))
+            .par_iter()
+            .capacity(4)
+            .take(3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // THEN they are the first documents
+        assert_eq!(
+            docs,
+            vec![TestDoc { id: 0 }, TestDoc { id: 1 }, TestDoc { id: 2 }]
+        );
+    }
+
+    /// A document that takes a while to deserialize if its id is 0.
+    #[derive(Debug)]
+    struct SlowDoc;
+
+    impl<'de> Deserialize<'de> for SlowDoc {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if TestDoc::deserialize(deserializer)?.id == 0 {
+                thread::sleep(std::time::Duration::from_millis(200));
+            }
+            Ok(SlowDoc)
+        }
+    }
+
+    #[test]
+    fn test_par_iter_ordered_bounds_look_ahead() {
+        // GIVEN many documents where the first is slow to deserialize
+        let yaml = yaml(1000, 1000);
+
+        // WHEN the first document is yielded in order
+        let mut docs = LazyDocs::<SlowDoc, _>::from_reader(Cursor::new(yaml))
+            .par_iter()
+            .capacity(4)
+            .num_threads(4);
+        docs.next().unwrap().unwrap();
+
+        // THEN the documents deserialized ahead of it are within the capacity
+        assert!(docs.reorder.len() < 4);
+        assert_eq!(docs.count(), 999);
+    }
+}
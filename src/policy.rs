@@ -0,0 +1,240 @@
+//! Provides the policies for handling documents that cannot be deserialized during lazy
+//! iteration.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::lazy::RawDoc;
+use crate::{DocumentError, LimitError};
+
+/// What iterating [crate::lazy::LazyDocs] or [crate::lazy::LazyValues] does with a document that
+/// cannot be deserialized.
+///
+/// The policy applies when iterating them directly. Their `try_iter` versions yield every error
+/// to the caller instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Skips the document. This is the default.
+    #[default]
+    Skip,
+    /// Skips the document after appending its raw text to the quarantine file at the path,
+    /// creating it if needed. The failure is kept in the [Summary], so that it is not lost if
+    /// the quarantine file cannot be written.
+    Quarantine(PathBuf),
+    /// Ends iteration at the document. The failure is kept in the [Summary].
+    Stop,
+    /// Skips the document and keeps the failure in the [Summary].
+    Collect,
+}
+
+/// A summary of the documents read by a lazy iterator.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::lazy::LazyDocs;
+/// use syt::policy::ErrorPolicy;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct MyDoc {
+///     id: u32,
+/// }
+///
+/// let yaml = "id: 1\n---\nname: no id\n---\nid: 3\n";
+/// let mut docs = LazyDocs::<MyDoc, _>::from_str(yaml).error_policy(ErrorPolicy::Collect);
+///
+/// let ids = docs.by_ref().map(|doc| doc.id).collect::<Vec<_>>();
+/// assert_eq!(ids, vec![1, 3]);
+///
+/// let summary = docs.summary();
+/// assert_eq!(summary.read, 3);
+/// assert_eq!(summary.failed, 1);
+/// assert_eq!(summary.failures[0].index, 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// The number of documents read, including those that failed.
+    pub read: usize,
+    /// The number of documents that could not be deserialized.
+    pub failed: usize,
+    /// The failures kept by [ErrorPolicy::Quarantine], [ErrorPolicy::Stop] and
+    /// [ErrorPolicy::Collect].
+    pub failures: Vec<DocumentError>,
+    /// The I/O error that ended iteration, either reading the source or writing to the
+    /// quarantine file.
+    pub io_error: Option<io::Error>,
+    /// The limit that ended iteration, if it was read with [crate::limits::Limits].
+    pub limit_error: Option<LimitError>,
+}
+
+/// Applies an [ErrorPolicy] and keeps the [Summary] for an iterator.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorHandler {
+    pub(crate) policy: ErrorPolicy,
+    pub(crate) summary: Summary,
+    stopped: bool,
+}
+
+impl ErrorHandler {
+    /// Whether iteration has ended because of the policy or an error.
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Records a document that was deserialized.
+    pub(crate) fn ok(&mut self) {
+        self.summary.read += 1;
+    }
+
+    /// Records an error reading the documents, which ends iteration.
+    pub(crate) fn error(&mut self, err: crate::Error) {
+        match err {
+            crate::Error::LimitExceeded(err) => self.summary.limit_error = Some(err),
+            crate::Error::IoError(err) => self.summary.io_error = Some(err),
+            err => self.summary.io_error = Some(io::Error::other(err)),
+        }
+        self.stopped = true;
+    }
+
+    /// Records a document that could not be deserialized and applies the policy to it.
+    pub(crate) fn failed(&mut self, raw: &RawDoc, err: serde_yml::Error) {
+        self.summary.read += 1;
+        self.summary.failed += 1;
+        match &self.policy {
+            ErrorPolicy::Skip => {}
+            ErrorPolicy::Quarantine(path) => {
+                let result = quarantine(path, &raw.text);
+                self.summary.failures.push(raw.document_error(err));
+                if let Err(err) = result {
+                    self.error(err.into());
+                }
+            }
+            ErrorPolicy::Stop => {
+                self.summary.failures.push(raw.document_error(err));
+                self.stopped = true;
+            }
+            ErrorPolicy::Collect => self.summary.failures.push(raw.document_error(err)),
+        }
+    }
+}
+
+/// Appends the text of a document to a quarantine file so that it can be read back as a document.
+fn quarantine(path: &Path, text: &str) -> io::Result<()> {
+    let mut file = File::options().append(true).create(true).open(path)?;
+    let mut buf = String::new();
+    if file.metadata()?.len() != 0 && !text.starts_with("---") && !text.starts_with('%') {
+        buf.push_str("---\n");
+    }
+    buf.push_str(text);
+    if !text.ends_with('\n') {
+        buf.push('\n');
+    }
+    file.write_all(buf.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::lazy::{LazyDocStart, LazyDocs, LazyValues};
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct TestDoc {
+        id: u32,
+    }
+
+    const YAML: &str = "id: 1\n---\nname: no id\n---\nid: 3\n--- [\n---\nid: 5";
+
+    #[test]
+    fn test_skip() {
+        // GIVEN documents with bad ones
+        let mut docs = LazyDocs::<TestDoc, _>::from_str(YAML);
+
+        // WHEN iterated with the default policy
+        let ids = docs.by_ref().map(|d| d.id).collect::<Vec<_>>();
+
+        // THEN bad documents are skipped and counted
+        assert_eq!(ids, vec![1, 3, 5]);
+        assert_eq!(docs.summary().read, 5);
+        assert_eq!(docs.summary().failed, 2);
+        assert!(docs.summary().failures.is_empty());
+    }
+
+    #[test]
+    fn test_stop() {
+        // GIVEN documents with bad ones
+        let mut docs = LazyDocs::<TestDoc, _>::from_str(YAML).error_policy(ErrorPolicy::Stop);
+
+        // WHEN iterated with the stop policy
+        let ids = docs.by_ref().map(|d| d.id).collect::<Vec<_>>();
+
+        // THEN iteration ends at the first bad document
+        assert_eq!(ids, vec![1]);
+        assert_eq!(docs.next(), None);
+        let summary = docs.summary();
+        assert_eq!((summary.read, summary.failed), (2, 1));
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].index, 1);
+        assert_eq!(summary.failures[0].start_line, 2);
+    }
+
+    #[test]
+    fn test_collect() {
+        // GIVEN documents with bad ones
+        let mut values = LazyValues::from_str(YAML).error_policy(ErrorPolicy::Collect);
+
+        // WHEN iterated with the collect policy
+        let count = values.by_ref().count();
+
+        // THEN every failure is reported at the end
+        assert_eq!(count, 4);
+        let summary = values.summary();
+        assert_eq!((summary.read, summary.failed), (5, 1));
+        assert_eq!(summary.failures[0].index, 3);
+    }
+
+    #[test]
+    fn test_quarantine() {
+        // GIVEN documents with bad ones
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quarantine.yml");
+        let mut docs = LazyDocs::<TestDoc, _>::from_str(YAML)
+            .error_policy(ErrorPolicy::Quarantine(path.clone()));
+
+        // WHEN iterated with the quarantine policy
+        let ids = docs.by_ref().map(|d| d.id).collect::<Vec<_>>();
+
+        // THEN bad documents are skipped and written to the quarantine file
+        assert_eq!(ids, vec![1, 3, 5]);
+        assert_eq!(docs.summary().failed, 2);
+        let indexes = docs.summary().failures.iter().map(|f| f.index);
+        assert_eq!(indexes.collect::<Vec<_>>(), vec![1, 3]);
+        let quarantined = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(quarantined, "---\nname: no id\n--- [\n");
+        let docs = LazyDocStart::new(&path).unwrap().collect::<Vec<_>>();
+        assert_eq!(docs, vec!["---\nname: no id\n", "--- [\n"]);
+    }
+
+    #[test]
+    fn test_quarantine_error() {
+        // GIVEN a quarantine file that cannot be created
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("quarantine.yml");
+        let mut docs =
+            LazyDocs::<TestDoc, _>::from_str(YAML).error_policy(ErrorPolicy::Quarantine(path));
+
+        // WHEN iterated
+        let ids = docs.by_ref().map(|d| d.id).collect::<Vec<_>>();
+
+        // THEN iteration ends with the error and the failure that was not quarantined in the
+        // summary
+        assert_eq!(ids, vec![1]);
+        let summary = docs.summary();
+        assert!(summary.io_error.is_some());
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].index, 1);
+        assert_eq!(summary.failures[0].start_line, 2);
+    }
+}
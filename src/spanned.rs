@@ -0,0 +1,170 @@
+//! Provides lazily read YAML documents along with where they were found in the source.
+use std::io::{BufRead, BufReader};
+
+use serde::de::DeserializeOwned;
+
+use crate::compress::FileSource;
+use crate::lazy::LazyDocs;
+use crate::policy::Summary;
+
+/// A deserialized YAML document and its location in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    /// The deserialized document.
+    pub value: T,
+    /// The zero-based index of the document in the stream, as in [crate::DocumentError].
+    pub index: usize,
+    /// The byte offset of the start of the document. For compressed files, this is the offset in
+    /// the decompressed text.
+    pub offset: u64,
+    /// The one-based line on which the document starts.
+    pub start_line: usize,
+    /// The one-based line on which the document ends (inclusive).
+    pub end_line: usize,
+    /// The raw text of the document, if requested with [SpannedDocs::with_raw].
+    ///
+    /// This is the exact text yielded by [crate::lazy::LazyDocStart], including its line endings
+    /// and any `---` line that begins it.
+    pub raw: Option<String>,
+}
+
+impl<T: DeserializeOwned, R: BufRead> LazyDocs<T, R> {
+    /// Converts this iterator into one that yields each document with its location.
+    ///
+    /// See [SpannedDocs].
+    pub fn spanned(self) -> SpannedDocs<T, R> {
+        SpannedDocs {
+            docs: self,
+            with_raw: false,
+        }
+    }
+}
+
+/// An iterator over YAML documents and their locations, created with [LazyDocs::spanned].
+///
+/// Documents that cannot be deserialized are handled with the [crate::policy::ErrorPolicy] of
+/// the [LazyDocs] it was created from.
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::lazy::LazyDocs;
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct MyDoc {
+///     id: u32,
+/// }
+///
+/// let yaml = "id: 1\n---\nid: 2\n";
+/// let mut docs = LazyDocs::<MyDoc, _>::from_str(yaml).spanned().with_raw(true);
+///
+/// let doc = docs.next().unwrap();
+/// assert_eq!(doc.value, MyDoc { id: 1 });
+///
+/// let doc = docs.next().unwrap();
+/// assert_eq!(doc.value, MyDoc { id: 2 });
+/// assert_eq!((doc.index, doc.offset, doc.start_line, doc.end_line), (1, 6, 2, 3));
+/// assert_eq!(doc.raw.as_deref(), Some("---\nid: 2\n"));
+/// ```
+pub struct SpannedDocs<T: DeserializeOwned, R: BufRead = BufReader<FileSource>> {
+    docs: LazyDocs<T, R>,
+    with_raw: bool,
+}
+
+impl<T: DeserializeOwned, R: BufRead> SpannedDocs<T, R> {
+    /// Sets whether the raw text of each document is kept in [Spanned::raw].
+    pub fn with_raw(mut self, with_raw: bool) -> Self {
+        self.with_raw = with_raw;
+        self
+    }
+
+    /// The summary of the documents read so far.
+    pub fn summary(&self) -> &Summary {
+        self.docs.summary()
+    }
+}
+
+impl<T: DeserializeOwned, R: BufRead> Iterator for SpannedDocs<T, R> {
+    type Item = Spanned<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (raw, value) = self.docs.next_parsed()?;
+        Some(Spanned {
+            value,
+            index: raw.index,
+            offset: raw.offset,
+            start_line: raw.start_line,
+            end_line: raw.end_line,
+            raw: self.with_raw.then_some(raw.text),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::policy::ErrorPolicy;
+
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct TestDoc {
+        id: u32,
+    }
+
+    #[test]
+    fn test_spanned_skips_bad_docs() {
+        // GIVEN a bad document between good ones
+        let yaml = "# first\nid: 1\n---\nname: no id\n---\nid: 3\n";
+
+        // WHEN read spanned
+        let docs = LazyDocs::<TestDoc, _>::from_str(yaml)
+            .spanned()
+            .collect::<Vec<_>>();
+
+        // THEN the good documents have their locations and no raw text
+        assert_eq!(
+            docs,
+            vec![
+                Spanned {
+                    value: TestDoc { id: 1 },
+                    index: 0,
+                    offset: 0,
+                    start_line: 1,
+                    end_line: 2,
+                    raw: None,
+                },
+                Spanned {
+                    value: TestDoc { id: 3 },
+                    index: 2,
+                    offset: 30,
+                    start_line: 5,
+                    end_line: 6,
+                    raw: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spanned_raw_rewrites_document() {
+        // GIVEN documents
+        let yaml = "id: 1\n---\nid: 2\n---\nid: 3\n";
+
+        // WHEN read spanned with raw text
+        let mut docs = LazyDocs::<TestDoc, _>::from_str(yaml)
+            .error_policy(ErrorPolicy::Stop)
+            .spanned()
+            .with_raw(true);
+        let spans = docs.by_ref().collect::<Vec<_>>();
+
+        // THEN the raw text can be used to rewrite just one document
+        let doc = &spans[1];
+        let start = doc.offset as usize;
+        let end = start + doc.raw.as_ref().unwrap().len();
+        let rewritten = format!("{}---\nid: 20\n{}", &yaml[..start], &yaml[end..]);
+        assert_eq!(rewritten, "id: 1\n---\nid: 20\n---\nid: 3\n");
+        assert_eq!(docs.summary().read, 3);
+    }
+}
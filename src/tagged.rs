@@ -0,0 +1,403 @@
+//! Provides dispatch of YAML documents to types by the tag on their `---` line.
+//!
+//! Streams that mix kinds of documents can mark each with a tag on its separator line:
+//!
+//! ```yaml
+//! --- !Deployment
+//! name: web
+//! --- !Service
+//! port: 80
+//! ```
+//!
+//! A [TagRegistry] maps each tag to the type its documents are deserialized into, and a
+//! [TaggedDocs] iterator yields every document converted into a common type, such as an enum or a
+//! boxed trait object. Documents can be appended with a tag with
+//! [crate::append::append_or_new_tagged].
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::boundary::{marker_rest, trim_line_ending};
+use crate::compress::FileSource;
+use crate::lazy::LazyDocStart;
+
+type Handler<D> = Box<dyn Fn(Option<&str>, &str) -> Result<D, serde_yml::Error>>;
+
+/// A registry of the types that documents are deserialized into according to their tag.
+///
+/// Tags are registered as they are written, including the leading `!`, such as `!Deployment`.
+pub struct TagRegistry<D> {
+    handlers: HashMap<String, Handler<D>>,
+    default: Option<Handler<D>>,
+}
+
+impl<D> Default for TagRegistry<D> {
+    fn default() -> Self {
+        TagRegistry {
+            handlers: HashMap::new(),
+            default: None,
+        }
+    }
+}
+
+impl<D> fmt::Debug for TagRegistry<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TagRegistry")
+            .field("tags", &self.handlers.keys().collect::<Vec<_>>())
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+impl<D: 'static> TagRegistry<D> {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the type `T` for documents with `tag`, converting each into `D` with `f`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag, including the leading `!`.
+    /// * `f` - Converts the deserialized document, such as an enum variant constructor.
+    pub fn register<T, F>(mut self, tag: &str, f: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(T) -> D + 'static,
+    {
+        self.handlers.insert(
+            tag.to_string(),
+            Box::new(move |_, text| serde_yml::from_str::<T>(text).map(&f)),
+        );
+        self
+    }
+
+    /// Registers the type `T` for documents without a tag or with a tag that is not registered,
+    /// converting each into `D` with `f` along with its tag.
+    pub fn default_handler<T, F>(mut self, f: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(Option<&str>, T) -> D + 'static,
+    {
+        self.default = Some(Box::new(move |tag, text| {
+            serde_yml::from_str::<T>(text).map(|t| f(tag, t))
+        }));
+        self
+    }
+
+    /// Deserializes the text of a document according to its tag.
+    fn deserialize(&self, text: &str) -> Result<D, serde_yml::Error> {
+        let (tag, text) = split_tag(text);
+        let handler = tag
+            .and_then(|tag| self.handlers.get(tag))
+            .or(self.default.as_ref());
+        match handler {
+            Some(handler) => handler(tag, &text),
+            None => Err(match tag {
+                Some(tag) => serde_yml::Error::custom(format!("no type is registered for {tag}")),
+                None => serde_yml::Error::custom("document has no tag and no default type"),
+            }),
+        }
+    }
+}
+
+/// Returns the tag on the `---` line of the text of a document, if it has one.
+///
+/// Example:
+///
+/// ```rust
+/// use syt::tagged::doc_tag;
+///
+/// assert_eq!(doc_tag("--- !Service\nport: 80\n"), Some("!Service"));
+/// assert_eq!(doc_tag("---\nport: 80\n"), None);
+/// ```
+pub fn doc_tag(text: &str) -> Option<&str> {
+    marker_line(text).and_then(|(_, line)| line_tag(line))
+}
+
+/// Finds the `---` line of a document and its offset.
+fn marker_line(text: &str) -> Option<(usize, &str)> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if marker_rest(trim_line_ending(line), "---").is_some() {
+            return Some((offset, line));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Returns the tag after the `---` of a marker line.
+fn line_tag(line: &str) -> Option<&str> {
+    let rest = marker_rest(trim_line_ending(line), "---")?.trim_start();
+    if !rest.starts_with('!') {
+        return None;
+    }
+    let end = rest.find([' ', '\t']).unwrap_or(rest.len());
+    Some(&rest[..end])
+}
+
+/// Splits the tag from the `---` line of a document, leaving the rest of the line in place so
+/// that line numbers are unchanged.
+fn split_tag(text: &str) -> (Option<&str>, Cow<'_, str>) {
+    let Some((offset, line)) = marker_line(text) else {
+        return (None, Cow::Borrowed(text));
+    };
+    let Some(tag) = line_tag(line) else {
+        return (None, Cow::Borrowed(text));
+    };
+    let tag_end = offset + line.find(tag).unwrap_or(0) + tag.len();
+    let mut stripped = String::with_capacity(text.len());
+    stripped.push_str(&text[..offset + "---".len()]);
+    stripped.push_str(&text[tag_end..]);
+    (Some(tag), Cow::Owned(stripped))
+}
+
+/// An iterator over YAML documents deserialized according to their tag with a [TagRegistry].
+///
+/// Every document is yielded as a [crate::Result]. A document that cannot be deserialized, or
+/// that has a tag with no registered type and there is no default, is yielded as a
+/// [crate::Error::DocumentError].
+///
+/// Example:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use syt::tagged::{TagRegistry, TaggedDocs};
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Deployment {
+///     name: String,
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq, Eq)]
+/// struct Service {
+///     port: u16,
+/// }
+///
+/// #[derive(Debug, PartialEq, Eq)]
+/// enum Kind {
+///     Deployment(Deployment),
+///     Service(Service),
+/// }
+///
+/// let registry = TagRegistry::new()
+///     .register("!Deployment", Kind::Deployment)
+///     .register("!Service", Kind::Service);
+///
+/// let yaml = "--- !Deployment\nname: web\n--- !Service\nport: 80\n";
+/// let docs = TaggedDocs::from_str(yaml, registry)
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(
+///     docs,
+///     vec![
+///         Kind::Deployment(Deployment { name: "web".to_string() }),
+///         Kind::Service(Service { port: 80 }),
+///     ]
+/// );
+/// ```
+pub struct TaggedDocs<D, R: BufRead = BufReader<FileSource>> {
+    doc_start: LazyDocStart<R>,
+    registry: TagRegistry<D>,
+}
+
+impl<D: 'static> TaggedDocs<D> {
+    /// Creates a new `TaggedDocs` iterator over the YAML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn new(path: &Path, registry: TagRegistry<D>) -> crate::Result<Self> {
+        Ok(Self::from_doc_start(LazyDocStart::new(path)?, registry))
+    }
+}
+
+impl<'a, D: 'static> TaggedDocs<D, &'a [u8]> {
+    /// Creates a new `TaggedDocs` iterator over the YAML documents in a string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &'a str, registry: TagRegistry<D>) -> Self {
+        Self::from_reader(s.as_bytes(), registry)
+    }
+}
+
+impl<D: 'static, R: BufRead> TaggedDocs<D, R> {
+    /// Creates a new `TaggedDocs` iterator over the YAML documents read from `reader`.
+    pub fn from_reader(reader: R, registry: TagRegistry<D>) -> Self {
+        Self::from_doc_start(LazyDocStart::from_reader(reader), registry)
+    }
+
+    fn from_doc_start(doc_start: LazyDocStart<R>, registry: TagRegistry<D>) -> Self {
+        TaggedDocs {
+            doc_start,
+            registry,
+        }
+    }
+}
+
+impl<D: 'static, R: BufRead> Iterator for TaggedDocs<D, R> {
+    type Item = crate::Result<D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let raw = match self.doc_start.next_raw()? {
+            Ok(raw) => raw,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(
+            self.registry
+                .deserialize(&raw.text)
+                .map_err(|err| raw.error(err)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::append::{append_or_new, append_or_new_tagged};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Deployment {
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Service {
+        port: u16,
+    }
+
+    trait Resource {
+        fn describe(&self) -> String;
+    }
+
+    impl Resource for Deployment {
+        fn describe(&self) -> String {
+            format!("deployment {}", self.name)
+        }
+    }
+
+    impl Resource for Service {
+        fn describe(&self) -> String {
+            format!("service on {}", self.port)
+        }
+    }
+
+    impl Resource for serde_yml::Value {
+        fn describe(&self) -> String {
+            "other".to_string()
+        }
+    }
+
+    fn registry() -> TagRegistry<Box<dyn Resource>> {
+        TagRegistry::new()
+            .register("!Deployment", |d: Deployment| {
+                Box::new(d) as Box<dyn Resource>
+            })
+            .register("!Service", |s: Service| Box::new(s) as Box<dyn Resource>)
+    }
+
+    #[test]
+    fn test_split_tag() {
+        // GIVEN documents with and without tags
+        // WHEN the tag is split
+        // THEN it is removed from the marker line only
+        assert_eq!(
+            split_tag("# c\n--- !Service\nport: 80\n"),
+            (Some("!Service"), Cow::Borrowed("# c\n---\nport: 80\n"))
+        );
+        assert_eq!(
+            split_tag("--- !Service {port: 80}\n"),
+            (Some("!Service"), Cow::Borrowed("--- {port: 80}\n"))
+        );
+        assert_eq!(
+            split_tag("---\nport: !x 80\n"),
+            (None, Cow::Borrowed("---\nport: !x 80\n"))
+        );
+        assert_eq!(split_tag("port: 80\n"), (None, Cow::Borrowed("port: 80\n")));
+    }
+
+    #[test]
+    fn test_trait_objects_with_default() {
+        // GIVEN documents with known, unknown and no tags
+        let yaml =
+            "--- !Deployment\nname: web\n--- !Service\nport: 80\n--- !Other\na: 1\n---\nb: 2\n";
+        let registry = registry().default_handler(|tag, value: serde_yml::Value| {
+            assert!(tag.is_none() || tag == Some("!Other"));
+            Box::new(value) as Box<dyn Resource>
+        });
+
+        // WHEN read tagged
+        let docs = TaggedDocs::from_str(yaml, registry)
+            .map(|doc| doc.unwrap().describe())
+            .collect::<Vec<_>>();
+
+        // THEN each is dispatched by its tag
+        assert_eq!(
+            docs,
+            vec!["deployment web", "service on 80", "other", "other"]
+        );
+    }
+
+    #[test]
+    fn test_unregistered_tag_without_default() {
+        // GIVEN a document with an unregistered tag
+        let yaml = "--- !Service\nport: 80\n--- !Other\na: 1\n";
+
+        // WHEN read tagged without a default
+        let docs = TaggedDocs::from_str(yaml, registry()).collect::<Vec<_>>();
+
+        // THEN it is a document error
+        assert!(docs[0].is_ok());
+        let Err(crate::Error::DocumentError(err)) = &docs[1] else {
+            panic!("expected a document error");
+        };
+        assert_eq!((err.index, err.start_line), (1, 3));
+        assert!(err.to_string().contains("!Other"));
+    }
+
+    #[test]
+    fn test_append_tagged() {
+        // GIVEN a new file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resources.yml");
+
+        // WHEN documents are appended with tags
+        append_or_new_tagged(&path, "!Deployment", Deployment { name: "web".into() }).unwrap();
+        append_or_new_tagged(&path, "!Service", Service { port: 80 }).unwrap();
+
+        // THEN the tags are on the separator lines
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            "--- !Deployment\nname: web\n\n--- !Service\nport: 80\n"
+        );
+        let docs = TaggedDocs::new(&path, registry())
+            .unwrap()
+            .map(|doc| doc.unwrap().describe())
+            .collect::<Vec<_>>();
+        assert_eq!(docs, vec!["deployment web", "service on 80"]);
+    }
+
+    #[test]
+    fn test_append_invalid_tag() {
+        // GIVEN a file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resources.yml");
+        append_or_new(&path, Service { port: 80 }).unwrap();
+
+        // WHEN a document is appended with an invalid tag
+        let result = append_or_new_tagged(&path, "Service two", Service { port: 81 });
+
+        // THEN it is an error and nothing is written
+        assert!(matches!(result, Err(crate::Error::IoError(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "port: 80\n");
+    }
+}